@@ -0,0 +1,109 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::jvozba::JvozbaError;
+use crate::valsi::ValsiClass;
+
+/// Errors returned by the lujvo functions of this crate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VlazbaError {
+    /// A lujvo needs at least two rafsi
+    TooFewValsi { count: usize },
+    /// The word cannot be split into rafsi; `offset` is the byte position where splitting failed
+    NotDecomposable { lujvo: String, offset: usize },
+    /// The word splits into rafsi, but the canonical lujvo for those rafsi is `correct`
+    NonCanonical { lujvo: String, correct: String },
+    /// The word is a lujvo, but a CV cmavo in front of it would also form one
+    Slinkuhi { lujvo: String },
+    /// No selrafsi is known for a rafsi at byte position `offset`
+    UnknownRafsi { rafsi: String, offset: usize },
+    /// jvozba produced no lujvo for the selrafsi
+    NoLujvo { selrafsi: Vec<String> },
+    /// The jvozba search exceeded its limits
+    SearchLimit(JvozbaError),
+    /// Some words given to jvozba cannot be turned into rafsi
+    InvalidSelrafsi(Vec<SelrafsiError>),
+}
+
+/// A word given to jvozba that cannot be used as a selrafsi
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelrafsiError {
+    pub word: String,
+    /// Position of the word in the tanru, starting from 0
+    pub position: usize,
+    pub problem: SelrafsiProblem,
+}
+
+/// Why a word cannot be used as a selrafsi
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelrafsiProblem {
+    /// A cmavo without any rafsi
+    CmavoWithoutRafsi,
+    /// Neither a gismu nor a word with rafsi in the word lists; this is what it is instead
+    NotGismu(ValsiClass),
+}
+
+impl fmt::Display for SelrafsiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{{}}} (word {}): ", self.word, self.position + 1)?;
+        match &self.problem {
+            SelrafsiProblem::CmavoWithoutRafsi => write!(f, "cmavo without rafsi"),
+            SelrafsiProblem::NotGismu(ValsiClass::Invalid(reason)) => write!(f, "{}", reason),
+            SelrafsiProblem::NotGismu(class) => {
+                let class = match class {
+                    ValsiClass::Lujvo => "a lujvo",
+                    ValsiClass::Cmavo | ValsiClass::CmavoCompound(_) => "cmavo",
+                    ValsiClass::Cmevla => "a cmevla",
+                    ValsiClass::Fuhivla => "a fu'ivla",
+                    _ => "an unknown word",
+                };
+                write!(f, "{} without rafsi, not a gismu", class)
+            }
+        }
+    }
+}
+
+impl fmt::Display for VlazbaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VlazbaError::TooFewValsi { .. } => {
+                write!(f, "You need at least two valsi to make a lujvo")
+            }
+            VlazbaError::NotDecomposable { lujvo, offset } => {
+                write!(f, "Failed to decompose {{{}}} at position {}", lujvo, offset)
+            }
+            VlazbaError::NonCanonical { lujvo, correct } => {
+                write!(f, "malformed lujvo {{{}}}; it should be {{{}}}", lujvo, correct)
+            }
+            VlazbaError::Slinkuhi { lujvo } => {
+                write!(f, "{{{}}} fails the slinku'i test: {{pa{}}} is a lujvo", lujvo, lujvo)
+            }
+            VlazbaError::UnknownRafsi { rafsi, offset } => {
+                write!(f, "Unknown rafsi {{{}}} at position {}", rafsi, offset)
+            }
+            VlazbaError::NoLujvo { selrafsi } => {
+                write!(f, "Failed to build a lujvo from {{{}}}", selrafsi.join(" "))
+            }
+            VlazbaError::SearchLimit(e) => write!(f, "jvozba search too large: {}", e),
+            VlazbaError::InvalidSelrafsi(errors) => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "No rafsi for {}", errors.join("; "))
+            }
+        }
+    }
+}
+
+impl Error for VlazbaError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            VlazbaError::SearchLimit(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<JvozbaError> for VlazbaError {
+    fn from(e: JvozbaError) -> Self {
+        VlazbaError::SearchLimit(e)
+    }
+}
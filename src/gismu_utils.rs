@@ -1,6 +1,9 @@
 use once_cell::sync::Lazy;
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
+use serde::Serialize;
 use smallvec::SmallVec;
+use std::borrow::Cow;
 use std::collections::HashSet;
 
 use crate::libs::config::{
@@ -66,8 +69,11 @@ impl GismuGenerator {
     }
 
     pub fn iterator(&self) -> Vec<String> {
-        self.shape_strings
-            .par_iter()
+        #[cfg(feature = "parallel")]
+        let shape_strings = self.shape_strings.par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let shape_strings = self.shape_strings.iter();
+        shape_strings
             .flat_map(|shape_string| self.shape_iterator(shape_string))
             .collect()
     }
@@ -76,8 +82,10 @@ impl GismuGenerator {
         let shape = self.shape_for_string(shape_string);
         let validator = self.shape_validator(shape_string);
     
-        (0..shape.iter().map(|v| v.len()).product::<usize>())
-            .into_par_iter()
+        let indices = 0..shape.iter().map(|v| v.len()).product::<usize>();
+        #[cfg(feature = "parallel")]
+        let indices = indices.into_par_iter();
+        indices
             .filter_map(move |index| {
                 let mut candidate = String::with_capacity(shape.len());
                 let mut remaining = index;
@@ -106,7 +114,7 @@ impl GismuGenerator {
             .collect()
     }
 
-    fn shape_validator(&self, shape: &str) -> impl Fn(&str) -> bool {
+    pub(crate) fn shape_validator(&self, shape: &str) -> impl Fn(&str) -> bool {
         type Predicate = Box<dyn Fn(&str) -> bool + Send + Sync>;
 
         let predicates: Vec<Predicate> = shape
@@ -160,6 +168,23 @@ impl GismuGenerator {
     }
 }
 
+/// A gismu candidate with its score
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GismuCandidate {
+    pub candidate: String,
+    /// Weighted sum of `language_scores`
+    pub score: f32,
+    /// Similarity to each input word, in input order
+    pub language_scores: Vec<f32>,
+}
+
+/// The existing gismu too similar to a candidate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SimilarGismu {
+    pub candidate: String,
+    pub similar: Vec<String>,
+}
+
 pub struct GismuScorer<'a> {
     input_words: &'a [String],
     weights: SmallVec<[f32; 6]>,
@@ -200,6 +225,15 @@ impl<'a> GismuScorer<'a> {
         (weighted_sum, candidate, similarity_scores)
     }
 
+    pub fn score_candidate(&self, candidate: &str) -> GismuCandidate {
+        let (score, language_scores) = self.compute_score(candidate);
+        GismuCandidate {
+            candidate: candidate.to_string(),
+            score,
+            language_scores: language_scores.to_vec(),
+        }
+    }
+
     fn score_dyad_by_pattern(&self, candidate: &str, input_word: &str) -> f32 {
         let l = candidate.len();
         let iw02: String = input_word.chars().step_by(2).collect();
@@ -237,15 +271,32 @@ impl<'a> GismuScorer<'a> {
     }
 }
 
+/// Whether a gimka candidate is made only of Lojban letters, which are all ASCII
+pub fn is_lojban_candidate(candidate: &str) -> bool {
+    let candidate = candidate.trim_end();
+    !candidate.is_empty()
+        && candidate
+            .chars()
+            .all(|c| "abcdefgijklmnoprstuvxyz'".contains(c.to_ascii_lowercase()))
+}
+
 pub struct GismuMatcher<'a> {
-    gismus: &'a [String],
+    gismus: Cow<'a, [String]>,
     stem_length: usize,
 }
 
 impl<'a> GismuMatcher<'a> {
     pub fn new(gismus: &'a [String], stem_length: Option<usize>) -> Self {
         Self {
-            gismus,
+            gismus: Cow::Borrowed(gismus),
+            stem_length: stem_length.unwrap_or(4),
+        }
+    }
+
+    /// A matcher that keeps its gismu list, e.g. to be built once and stored
+    pub fn owned(gismus: Vec<String>, stem_length: Option<usize>) -> GismuMatcher<'static> {
+        GismuMatcher {
+            gismus: Cow::Owned(gismus),
             stem_length: stem_length.unwrap_or(4),
         }
     }
@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::inventory::is_well_formed_short_rafsi;
+use super::rafsi_list::{all_rafsi_lists, RafsiList};
+use super::tools::RafsiOptions;
+
+/// A selrafsi together with the rafsi map that assigns it a rafsi
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RafsiOwner {
+    pub selrafsi: String,
+    pub list: RafsiList,
+}
+
+impl fmt::Display for RafsiOwner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{{}}} ({})", self.selrafsi, self.list)
+    }
+}
+
+/// A problem found in the rafsi maps by [`audit_rafsi`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditIssue {
+    /// The rafsi belongs to more than one selrafsi
+    DuplicateRafsi { rafsi: String, owners: Vec<RafsiOwner> },
+    /// An experimental rafsi that is already an official rafsi of another selrafsi
+    ShadowsOfficial {
+        rafsi: String,
+        experimental: RafsiOwner,
+        official: RafsiOwner,
+    },
+    /// The rafsi is not CVC, CCV, CVV or CV'V, or breaks the rules of its shape,
+    /// e.g. a CCV rafsi whose consonant pair cannot start a word
+    BadShape { rafsi: String, owner: RafsiOwner },
+    /// A gismu rafsi uses letters the gismu does not have
+    NotDerivable { rafsi: String, gismu: RafsiOwner },
+    /// A cmavo and a gismu share the rafsi
+    CmavoGismuCollision {
+        rafsi: String,
+        cmavo: RafsiOwner,
+        gismu: RafsiOwner,
+    },
+}
+
+impl fmt::Display for AuditIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuditIssue::DuplicateRafsi { rafsi, owners } => {
+                let owners: Vec<String> = owners.iter().map(|o| o.to_string()).collect();
+                write!(f, "-{}- belongs to {}", rafsi, owners.join(", "))
+            }
+            AuditIssue::ShadowsOfficial { rafsi, experimental, official } => write!(
+                f,
+                "experimental -{}- of {} shadows the official rafsi of {}",
+                rafsi, experimental, official
+            ),
+            AuditIssue::BadShape { rafsi, owner } => {
+                write!(f, "-{}- of {} is not a well-formed CVC, CCV, CVV or CV'V rafsi", rafsi, owner)
+            }
+            AuditIssue::NotDerivable { rafsi, gismu } => {
+                write!(f, "-{}- cannot be formed from the letters of {}", rafsi, gismu)
+            }
+            AuditIssue::CmavoGismuCollision { rafsi, cmavo, gismu } => {
+                write!(f, "-{}- is a rafsi of both {} and {}", rafsi, cmavo, gismu)
+            }
+        }
+    }
+}
+
+/// Check the bundled rafsi maps and the custom maps in `options` for conflicts and malformed rafsi
+///
+/// # Returns
+/// Every issue found, grouped by rafsi in alphabetical order
+pub fn audit_rafsi(options: &RafsiOptions) -> Vec<AuditIssue> {
+    let mut owners_of: BTreeMap<&str, Vec<RafsiOwner>> = BTreeMap::new();
+    for (list, map) in all_rafsi_lists(options) {
+        for (selrafsi, rafsi_list) in map {
+            for rafsi in rafsi_list {
+                owners_of.entry(rafsi.as_str()).or_default().push(RafsiOwner {
+                    selrafsi: selrafsi.clone(),
+                    list,
+                });
+            }
+        }
+    }
+
+    let mut issues = Vec::new();
+    for (rafsi, mut owners) in owners_of {
+        owners.sort();
+        for owner in &owners {
+            if !is_well_formed_short_rafsi(rafsi) {
+                issues.push(AuditIssue::BadShape {
+                    rafsi: rafsi.to_string(),
+                    owner: owner.clone(),
+                });
+            }
+            if !owner.list.is_cmavo() && !uses_letters_of(rafsi, &owner.selrafsi) {
+                issues.push(AuditIssue::NotDerivable {
+                    rafsi: rafsi.to_string(),
+                    gismu: owner.clone(),
+                });
+            }
+        }
+
+        // the same selrafsi may be listed in several maps, e.g. official and custom
+        let mut selrafsi: Vec<&str> = owners.iter().map(|o| o.selrafsi.as_str()).collect();
+        selrafsi.sort_unstable();
+        selrafsi.dedup();
+        if selrafsi.len() < 2 {
+            continue;
+        }
+        issues.push(AuditIssue::DuplicateRafsi {
+            rafsi: rafsi.to_string(),
+            owners: owners.clone(),
+        });
+
+        for a in &owners {
+            for b in owners.iter().filter(|b| b.selrafsi != a.selrafsi) {
+                if a.list.is_experimental() && !b.list.is_experimental() {
+                    issues.push(AuditIssue::ShadowsOfficial {
+                        rafsi: rafsi.to_string(),
+                        experimental: a.clone(),
+                        official: b.clone(),
+                    });
+                }
+                if a.list.is_cmavo() && !b.list.is_cmavo() {
+                    issues.push(AuditIssue::CmavoGismuCollision {
+                        rafsi: rafsi.to_string(),
+                        cmavo: a.clone(),
+                        gismu: b.clone(),
+                    });
+                }
+            }
+        }
+    }
+    issues
+}
+
+/// Whether every letter of `rafsi`, apostrophes aside, can be taken from a different letter of `word`
+///
+/// The order is not checked, since CCV rafsi may swap the first vowel and consonant of
+/// a CVCCV gismu, as in `valsi` → `vla`.
+fn uses_letters_of(rafsi: &str, word: &str) -> bool {
+    let mut letters: Vec<char> = word.chars().collect();
+    rafsi.chars().filter(|&c| c != '\'').all(|c| {
+        letters
+            .iter()
+            .position(|&w| w == c)
+            .map(|i| letters.remove(i))
+            .is_some()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn map(entries: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.iter().map(|s| s.to_string()).collect()))
+            .collect()
+    }
+
+    fn owner(selrafsi: &str, list: RafsiList) -> RafsiOwner {
+        RafsiOwner {
+            selrafsi: selrafsi.to_string(),
+            list,
+        }
+    }
+
+    #[test]
+    fn test_bundled_rafsi_are_consistent() {
+        let options = RafsiOptions {
+            exp_rafsi: true,
+            custom_cmavo: None,
+            custom_cmavo_exp: None,
+            custom_gismu: None,
+            custom_gismu_exp: None,
+        };
+        assert_eq!(audit_rafsi(&options), vec![]);
+    }
+
+    #[test]
+    fn test_audit_custom_maps() {
+        let custom_gismu_exp = map(&[
+            ("zbasu", &["kla", "zbasu"]),
+            ("valsi", &["vla"]),
+            ("kukte", &["kue"]),
+            ("tavla", &["tla"]),
+        ]);
+        let custom_cmavo = map(&[("nu", &["nun", "kla"]), ("na", &["nar"])]);
+        let options = RafsiOptions {
+            exp_rafsi: true,
+            custom_cmavo: Some(&custom_cmavo),
+            custom_cmavo_exp: None,
+            custom_gismu: None,
+            custom_gismu_exp: Some(&custom_gismu_exp),
+        };
+        let issues = audit_rafsi(&options);
+
+        let klama = owner("klama", RafsiList::Gismu);
+        let nu = owner("nu", RafsiList::CustomCmavo);
+        let zbasu = owner("zbasu", RafsiList::CustomGismuExp);
+        assert!(issues.contains(&AuditIssue::DuplicateRafsi {
+            rafsi: "kla".into(),
+            owners: vec![klama.clone(), nu.clone(), zbasu.clone()],
+        }));
+        assert!(issues.contains(&AuditIssue::ShadowsOfficial {
+            rafsi: "kla".into(),
+            experimental: zbasu.clone(),
+            official: klama.clone(),
+        }));
+        assert!(issues.contains(&AuditIssue::CmavoGismuCollision {
+            rafsi: "kla".into(),
+            cmavo: nu,
+            gismu: klama,
+        }));
+        assert!(issues.contains(&AuditIssue::NotDerivable {
+            rafsi: "kla".into(),
+            gismu: zbasu.clone(),
+        }));
+        assert!(issues.contains(&AuditIssue::BadShape {
+            rafsi: "zbasu".into(),
+            owner: zbasu,
+        }));
+        // shaped like CVV and CCV, but with a vowel pair and an initial pair Lojban does not allow
+        for (rafsi, gismu) in [("kue", "kukte"), ("tla", "tavla")] {
+            assert!(issues.contains(&AuditIssue::BadShape {
+                rafsi: rafsi.into(),
+                owner: owner(gismu, RafsiList::CustomGismuExp),
+            }));
+        }
+
+        // the same selrafsi in two maps, and rafsi with swapped letters, are fine
+        assert!(issues.iter().all(|i| !i.to_string().contains("-nar-")));
+        assert!(issues.iter().all(|i| !i.to_string().contains("-vla-")));
+    }
+}
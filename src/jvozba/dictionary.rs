@@ -0,0 +1,466 @@
+use std::collections::HashMap;
+
+use super::rafsi_index::{find_selrafsi, has_custom_maps, RafsiIndex, RafsiOrigin, SelrafsiCandidate};
+use super::rafsi_list::{
+    get_cmavo_rafsi_list, get_cmavo_rafsi_list_exp, get_gismu_rafsi_list, get_gismu_rafsi_list_exp,
+    RafsiList, RafsiMap,
+};
+use super::tools::{cmavo_rafsi_list, gismu_rafsi_list, RafsiOptions};
+
+/// Where jvozba and friends look up rafsi
+///
+/// Implemented by [`RafsiOptions`] and [`RafsiDictionary`]; applications can
+/// implement it to keep rafsi in their own storage.
+pub trait RafsiSource: Sync {
+    /// Rafsi of a cmavo, best first, or None if it has none
+    fn cmavo_rafsi(&self, cmavo: &str) -> Option<Vec<String>>;
+
+    /// Short rafsi of a gismu, best first; empty for gismu without them and unknown words
+    fn gismu_rafsi(&self, gismu: &str) -> Vec<String>;
+
+    /// Every selrafsi a rafsi may stand for, best first
+    ///
+    /// Besides their listed rafsi, gismu own their 4-letter rafsi and their full form.
+    fn selrafsi_candidates(&self, rafsi: &str) -> Vec<SelrafsiCandidate>;
+
+    /// An index to answer many [`RafsiSource::selrafsi_candidates`] lookups from, for
+    /// sources that would otherwise build one on every lookup
+    fn selrafsi_index(&self) -> Option<RafsiIndex> {
+        None
+    }
+}
+
+impl RafsiSource for RafsiOptions<'_> {
+    fn cmavo_rafsi(&self, cmavo: &str) -> Option<Vec<String>> {
+        cmavo_rafsi_list(cmavo, self.exp_rafsi, self.custom_cmavo, self.custom_cmavo_exp)
+    }
+
+    fn gismu_rafsi(&self, gismu: &str) -> Vec<String> {
+        gismu_rafsi_list(gismu, self.exp_rafsi, self.custom_gismu, self.custom_gismu_exp)
+            .unwrap_or_default()
+    }
+
+    /// With custom maps this builds the whole index; functions of this crate that look
+    /// up many rafsi use [`RafsiSource::selrafsi_index`] instead
+    fn selrafsi_candidates(&self, rafsi: &str) -> Vec<SelrafsiCandidate> {
+        find_selrafsi(rafsi, self)
+    }
+
+    fn selrafsi_index(&self) -> Option<RafsiIndex> {
+        has_custom_maps(self).then(|| RafsiIndex::new(self))
+    }
+}
+
+/// A rafsi source whose selrafsi lookups go through the index of
+/// [`RafsiSource::selrafsi_index`], built once
+pub(crate) struct Indexed<'a, S: ?Sized> {
+    source: &'a S,
+    index: Option<RafsiIndex>,
+}
+
+impl<'a, S: RafsiSource + ?Sized> Indexed<'a, S> {
+    pub(crate) fn new(source: &'a S) -> Self {
+        Indexed {
+            index: source.selrafsi_index(),
+            source,
+        }
+    }
+}
+
+impl<S: RafsiSource + ?Sized> RafsiSource for Indexed<'_, S> {
+    fn cmavo_rafsi(&self, cmavo: &str) -> Option<Vec<String>> {
+        self.source.cmavo_rafsi(cmavo)
+    }
+
+    fn gismu_rafsi(&self, gismu: &str) -> Vec<String> {
+        self.source.gismu_rafsi(gismu)
+    }
+
+    fn selrafsi_candidates(&self, rafsi: &str) -> Vec<SelrafsiCandidate> {
+        match &self.index {
+            Some(index) => index.candidates(rafsi).to_vec(),
+            None => self.source.selrafsi_candidates(rafsi),
+        }
+    }
+}
+
+/// How a [`RafsiLayer`] combines with the layers below it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerMode {
+    /// The layer's map replaces the whole map of the same kind below it
+    Replace,
+    /// The layer's rafsi are added after the rafsi a word already has
+    Merge,
+}
+
+/// One layer of a [`RafsiDictionary`]
+///
+/// An empty gismu or cmavo map leaves that kind of word untouched, also in
+/// [`LayerMode::Replace`] mode.
+#[derive(Debug, Clone)]
+pub struct RafsiLayer {
+    pub origin: RafsiOrigin,
+    pub mode: LayerMode,
+    pub gismu: RafsiMap,
+    pub cmavo: RafsiMap,
+}
+
+impl RafsiLayer {
+    /// The bundled official rafsi
+    pub fn official() -> Self {
+        RafsiLayer {
+            origin: RafsiOrigin::Official,
+            mode: LayerMode::Replace,
+            gismu: get_gismu_rafsi_list().clone(),
+            cmavo: get_cmavo_rafsi_list().clone(),
+        }
+    }
+
+    /// The bundled experimental rafsi, merged into the layers below
+    pub fn experimental() -> Self {
+        RafsiLayer {
+            origin: RafsiOrigin::Experimental,
+            mode: LayerMode::Merge,
+            gismu: get_gismu_rafsi_list_exp().clone(),
+            cmavo: get_cmavo_rafsi_list_exp().clone(),
+        }
+    }
+
+    /// User-supplied rafsi
+    pub fn custom(mode: LayerMode, gismu: RafsiMap, cmavo: RafsiMap) -> Self {
+        RafsiLayer {
+            origin: RafsiOrigin::Custom,
+            mode,
+            gismu,
+            cmavo,
+        }
+    }
+}
+
+/// Owned rafsi lookup built from ordered layers
+///
+/// Later layers take precedence. The layers are combined once when the
+/// dictionary is built, along with a reverse index for [`RafsiSource::selrafsi_candidates`].
+///
+/// # Examples
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use vlazba::jvozba::{dictionary::{LayerMode, RafsiDictionary, RafsiLayer}, jvozba};
+///
+/// let overlay = HashMap::from([("gasnu".to_string(), vec!["gan".to_string()])]);
+/// let dictionary = RafsiDictionary::from_layers([
+///     RafsiLayer::official(),
+///     RafsiLayer::custom(LayerMode::Merge, overlay, HashMap::new()),
+/// ]);
+/// let words = ["klama".to_string(), "gasnu".to_string()];
+/// assert_eq!(jvozba(&words, false, false, &dictionary)[0].lujvo, "klagau");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RafsiDictionary {
+    gismu: HashMap<String, Entry>,
+    cmavo: HashMap<String, Entry>,
+    index: RafsiIndex,
+}
+
+/// The rafsi of a word, each with the layer that added it
+#[derive(Debug, Clone)]
+struct Entry {
+    /// Origin of the layer that added the word
+    origin: RafsiOrigin,
+    rafsi: Vec<(String, RafsiOrigin)>,
+}
+
+impl RafsiDictionary {
+    pub fn from_layers(layers: impl IntoIterator<Item = RafsiLayer>) -> Self {
+        let mut dictionary = RafsiDictionary::default();
+        for layer in layers {
+            apply_layer(&mut dictionary.gismu, layer.gismu, layer.mode, layer.origin);
+            apply_layer(&mut dictionary.cmavo, layer.cmavo, layer.mode, layer.origin);
+        }
+
+        for (is_cmavo, map) in [(false, &dictionary.gismu), (true, &dictionary.cmavo)] {
+            for (selrafsi, entry) in map {
+                for (rafsi, origin) in &entry.rafsi {
+                    dictionary.index.insert(rafsi, selrafsi, rafsi_list_of(*origin, is_cmavo));
+                }
+                if !is_cmavo {
+                    dictionary
+                        .index
+                        .insert_gismu_forms(selrafsi, rafsi_list_of(entry.origin, false));
+                }
+            }
+        }
+        dictionary.index.sort();
+        dictionary
+    }
+
+    /// The bundled official rafsi
+    pub fn official() -> Self {
+        Self::from_layers([RafsiLayer::official()])
+    }
+
+    /// The bundled official and experimental rafsi
+    pub fn with_experimental() -> Self {
+        Self::from_layers([RafsiLayer::official(), RafsiLayer::experimental()])
+    }
+
+    /// A dictionary that looks up the same rafsi as [`RafsiOptions`] does as a [`RafsiSource`]
+    ///
+    /// Custom maps replace the bundled map of the same kind, and experimental maps
+    /// are only included with `exp_rafsi`. As in [`gismu_rafsi_list`], a word gets its
+    /// experimental rafsi only if it has no others; those it does not get still stand
+    /// for it in [`RafsiSource::selrafsi_candidates`].
+    pub fn from_options(options: &RafsiOptions) -> Self {
+        let origin = |custom: Option<&RafsiMap>| {
+            if custom.is_some() {
+                RafsiOrigin::Custom
+            } else {
+                RafsiOrigin::Official
+            }
+        };
+        let gismu = options.custom_gismu.unwrap_or(get_gismu_rafsi_list());
+        let cmavo = options.custom_cmavo.unwrap_or(get_cmavo_rafsi_list());
+        let mut layers = vec![
+            RafsiLayer {
+                origin: origin(options.custom_gismu),
+                mode: LayerMode::Merge,
+                gismu: gismu.clone(),
+                cmavo: RafsiMap::new(),
+            },
+            RafsiLayer {
+                origin: origin(options.custom_cmavo),
+                mode: LayerMode::Merge,
+                gismu: RafsiMap::new(),
+                cmavo: cmavo.clone(),
+            },
+        ];
+        let mut shadowed = Vec::new();
+        if options.exp_rafsi {
+            let exp_maps = [
+                (options.custom_gismu_exp, get_gismu_rafsi_list_exp(), gismu, false),
+                (options.custom_cmavo_exp, get_cmavo_rafsi_list_exp(), cmavo, true),
+            ];
+            for (custom, bundled, base, is_cmavo) in exp_maps {
+                let (used, unused): (RafsiMap, RafsiMap) = custom
+                    .unwrap_or(bundled)
+                    .iter()
+                    .map(|(word, rafsi)| (word.clone(), rafsi.clone()))
+                    .partition(|(word, _)| base.get(word).is_none_or(Vec::is_empty));
+                let list = match (custom.is_some(), is_cmavo) {
+                    (false, false) => RafsiList::GismuExp,
+                    (false, true) => RafsiList::CmavoExp,
+                    (true, false) => RafsiList::CustomGismuExp,
+                    (true, true) => RafsiList::CustomCmavoExp,
+                };
+                shadowed.extend(unused.into_iter().map(|(word, rafsi)| (word, rafsi, list)));
+                let (gismu, cmavo) = if is_cmavo { (RafsiMap::new(), used) } else { (used, RafsiMap::new()) };
+                layers.push(RafsiLayer {
+                    origin: if custom.is_some() { RafsiOrigin::Custom } else { RafsiOrigin::Experimental },
+                    mode: LayerMode::Merge,
+                    gismu,
+                    cmavo,
+                });
+            }
+        }
+
+        let mut dictionary = Self::from_layers(layers);
+        for (selrafsi, rafsi_list, list) in shadowed {
+            for rafsi in rafsi_list {
+                dictionary.index.insert(&rafsi, &selrafsi, list);
+            }
+        }
+        dictionary.index.sort();
+        dictionary
+    }
+}
+
+impl RafsiSource for RafsiDictionary {
+    fn cmavo_rafsi(&self, cmavo: &str) -> Option<Vec<String>> {
+        self.cmavo
+            .get(cmavo)
+            .filter(|entry| !entry.rafsi.is_empty())
+            .map(|entry| entry.rafsi.iter().map(|(r, _)| r.clone()).collect())
+    }
+
+    fn gismu_rafsi(&self, gismu: &str) -> Vec<String> {
+        self.gismu
+            .get(gismu)
+            .map(|entry| entry.rafsi.iter().map(|(r, _)| r.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    fn selrafsi_candidates(&self, rafsi: &str) -> Vec<SelrafsiCandidate> {
+        self.index.candidates(rafsi).to_vec()
+    }
+}
+
+fn apply_layer(
+    map: &mut HashMap<String, Entry>,
+    layer: RafsiMap,
+    mode: LayerMode,
+    origin: RafsiOrigin,
+) {
+    if layer.is_empty() {
+        return;
+    }
+    if mode == LayerMode::Replace {
+        map.clear();
+    }
+    for (word, rafsi_list) in layer {
+        let entry = map.entry(word).or_insert_with(|| Entry {
+            origin,
+            rafsi: Vec::new(),
+        });
+        for rafsi in rafsi_list {
+            if !entry.rafsi.iter().any(|(r, _)| *r == rafsi) {
+                entry.rafsi.push((rafsi, origin));
+            }
+        }
+    }
+}
+
+fn rafsi_list_of(origin: RafsiOrigin, is_cmavo: bool) -> RafsiList {
+    match (origin, is_cmavo) {
+        (RafsiOrigin::Official, false) => RafsiList::Gismu,
+        (RafsiOrigin::Official, true) => RafsiList::Cmavo,
+        (RafsiOrigin::Experimental, false) => RafsiList::GismuExp,
+        (RafsiOrigin::Experimental, true) => RafsiList::CmavoExp,
+        (RafsiOrigin::Custom, false) => RafsiList::CustomGismu,
+        (RafsiOrigin::Custom, true) => RafsiList::CustomCmavo,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(entries: &[(&str, &[&str])]) -> RafsiMap {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.iter().map(|s| s.to_string()).collect()))
+            .collect()
+    }
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_layers_merge_and_replace() {
+        assert_send_sync::<RafsiDictionary>();
+
+        let merged = RafsiDictionary::from_layers([
+            RafsiLayer::official(),
+            RafsiLayer::custom(LayerMode::Merge, map(&[("klama", &["kam"])]), RafsiMap::new()),
+        ]);
+        assert_eq!(merged.gismu_rafsi("klama"), vec!["kla", "kam"]);
+        assert_eq!(merged.gismu_rafsi("gasnu"), vec!["gau"]);
+        assert_eq!(merged.cmavo_rafsi("na"), Some(vec!["nar".to_string()]));
+        let candidates = merged.selrafsi_candidates("kam");
+        assert_eq!(candidates[0].selrafsi, "klama");
+        assert_eq!(candidates[0].origin(), RafsiOrigin::Custom);
+
+        let replaced = RafsiDictionary::from_layers([
+            RafsiLayer::official(),
+            RafsiLayer::custom(LayerMode::Replace, map(&[("klama", &["kam"])]), RafsiMap::new()),
+        ]);
+        assert_eq!(replaced.gismu_rafsi("klama"), vec!["kam"]);
+        assert!(replaced.gismu_rafsi("gasnu").is_empty());
+        assert!(replaced.selrafsi_candidates("kla").is_empty());
+        // the empty cmavo map leaves the official cmavo alone
+        assert_eq!(replaced.cmavo_rafsi("na"), Some(vec!["nar".to_string()]));
+    }
+
+    #[test]
+    fn test_dictionary_matches_options() {
+        let options = RafsiOptions {
+            exp_rafsi: true,
+            custom_cmavo: None,
+            custom_cmavo_exp: None,
+            custom_gismu: None,
+            custom_gismu_exp: None,
+        };
+        let dictionary = RafsiDictionary::with_experimental();
+        for word in ["klama", "gasnu", "zviki", "broda"] {
+            assert_eq!(dictionary.gismu_rafsi(word), options.gismu_rafsi(word), "{}", word);
+        }
+        for word in ["na", "xo'ei", "ku"] {
+            assert_eq!(dictionary.cmavo_rafsi(word), options.cmavo_rafsi(word), "{}", word);
+        }
+        for rafsi in ["kla", "gasn", "zvi", "zvik", "nar"] {
+            assert_eq!(
+                dictionary.selrafsi_candidates(rafsi),
+                options.selrafsi_candidates(rafsi),
+                "{}",
+                rafsi
+            );
+        }
+    }
+
+    #[test]
+    fn test_indexed_matches_source() {
+        let custom_gismu = map(&[("klama", &["kam"])]);
+        let options = RafsiOptions {
+            exp_rafsi: true,
+            custom_cmavo: None,
+            custom_cmavo_exp: None,
+            custom_gismu: Some(&custom_gismu),
+            custom_gismu_exp: None,
+        };
+        let indexed = Indexed::new(&options);
+        assert!(indexed.index.is_some());
+        for rafsi in ["kam", "kla", "zvi", "nar"] {
+            assert_eq!(indexed.selrafsi_candidates(rafsi), options.selrafsi_candidates(rafsi), "{}", rafsi);
+        }
+        assert!(Indexed::new(&RafsiDictionary::official()).index.is_none());
+    }
+
+    #[test]
+    fn test_from_options_with_custom_maps() {
+        let custom_gismu = map(&[("klama", &["kam"])]);
+        let custom_cmavo_exp = map(&[("xo'ei", &["xoz"])]);
+        let options = RafsiOptions {
+            exp_rafsi: true,
+            custom_cmavo: None,
+            custom_cmavo_exp: Some(&custom_cmavo_exp),
+            custom_gismu: Some(&custom_gismu),
+            custom_gismu_exp: None,
+        };
+        let dictionary = RafsiDictionary::from_options(&options);
+        for word in ["klama", "gasnu", "zviki"] {
+            assert_eq!(dictionary.gismu_rafsi(word), options.gismu_rafsi(word), "{}", word);
+        }
+        for word in ["na", "xo'ei"] {
+            assert_eq!(dictionary.cmavo_rafsi(word), options.cmavo_rafsi(word), "{}", word);
+        }
+        assert!(dictionary.selrafsi_candidates("kla").is_empty());
+        assert_eq!(dictionary.selrafsi_candidates("kam")[0].selrafsi, "klama");
+        assert_eq!(dictionary.selrafsi_candidates("xoz")[0].selrafsi, "xo'ei");
+    }
+
+    #[test]
+    fn test_from_options_falls_back_to_exp() {
+        let custom_gismu = map(&[("klama", &["kam"]), ("gasnu", &[])]);
+        let custom_gismu_exp = map(&[("klama", &["kal"]), ("gasnu", &["gan"])]);
+        let options = RafsiOptions {
+            exp_rafsi: true,
+            custom_cmavo: None,
+            custom_cmavo_exp: None,
+            custom_gismu: Some(&custom_gismu),
+            custom_gismu_exp: Some(&custom_gismu_exp),
+        };
+        let dictionary = RafsiDictionary::from_options(&options);
+        for word in ["klama", "gasnu"] {
+            assert_eq!(dictionary.gismu_rafsi(word), options.gismu_rafsi(word), "{}", word);
+        }
+        assert_eq!(dictionary.gismu_rafsi("klama"), vec!["kam"]);
+        assert_eq!(dictionary.gismu_rafsi("gasnu"), vec!["gan"]);
+        let words = ["klama".to_string(), "gasnu".to_string()];
+        assert_eq!(
+            crate::jvozba::jvozba(&words, false, false, &dictionary)[0].lujvo,
+            crate::jvozba::jvozba(&words, false, false, &options)[0].lujvo
+        );
+        // kal is not used for klama, but still read as klama
+        assert_eq!(dictionary.selrafsi_candidates("kal"), options.selrafsi_candidates("kal"));
+        assert_eq!(dictionary.selrafsi_candidates("kal")[0].selrafsi, "klama");
+    }
+}
@@ -0,0 +1,157 @@
+use super::dictionary::{Indexed, RafsiSource};
+use super::jvokaha::LujvoPartKind;
+use crate::libs::config::{C, V, VALID_CC_INITIALS};
+
+/// The shapes of short rafsi
+pub const SHORT_RAFSI_SHAPES: [LujvoPartKind; 4] = [
+    LujvoPartKind::Cvc,
+    LujvoPartKind::Ccv,
+    LujvoPartKind::Cvv,
+    LujvoPartKind::CvApostropheV,
+];
+
+/// The vowel pairs a CVV rafsi may end in; other pairs need an apostrophe
+const CVV_DIPHTHONGS: [&str; 4] = ["ai", "ei", "oi", "au"];
+
+/// Whether a rafsi is one of the [`possible_rafsi`] of its shape
+pub fn is_well_formed_short_rafsi(rafsi: &str) -> bool {
+    match LujvoPartKind::from_rafsi(rafsi) {
+        Some(LujvoPartKind::Cvc | LujvoPartKind::CvApostropheV) => true,
+        Some(LujvoPartKind::Ccv) => VALID_CC_INITIALS.contains(&&rafsi[..2]),
+        Some(LujvoPartKind::Cvv) => CVV_DIPHTHONGS.contains(&&rafsi[1..]),
+        _ => false,
+    }
+}
+
+/// Which free rafsi [`free_rafsi`] should report
+#[derive(Debug, Clone)]
+pub struct FreeRafsiFilter {
+    /// Shapes to include; all of [`SHORT_RAFSI_SHAPES`] by default
+    pub shapes: Vec<LujvoPartKind>,
+    /// Letters the rafsi must have at each position, `?` standing for any letter,
+    /// e.g. `k?a` or `?u'?`
+    pub pattern: Option<String>,
+}
+
+impl Default for FreeRafsiFilter {
+    fn default() -> Self {
+        FreeRafsiFilter {
+            shapes: SHORT_RAFSI_SHAPES.to_vec(),
+            pattern: None,
+        }
+    }
+}
+
+impl FreeRafsiFilter {
+    fn matches(&self, rafsi: &str, kind: LujvoPartKind) -> bool {
+        self.shapes.contains(&kind)
+            && self.pattern.as_ref().is_none_or(|pattern| {
+                pattern.chars().count() == rafsi.chars().count()
+                    && pattern.chars().zip(rafsi.chars()).all(|(p, c)| p == '?' || p == c)
+            })
+    }
+}
+
+/// Every string of the given short rafsi shape that Lojban phonotactics allow
+///
+/// CCV rafsi start with a permissible initial pair, and CVV rafsi end in one of the
+/// diphthongs `ai`, `ei`, `oi` and `au`; other vowel pairs need an apostrophe.
+/// Kinds other than [`SHORT_RAFSI_SHAPES`] give an empty list.
+pub fn possible_rafsi(kind: LujvoPartKind) -> Vec<String> {
+    let consonants: Vec<char> = C.chars().collect();
+    let vowels: Vec<char> = V.chars().collect();
+    let mut rafsi = Vec::new();
+    match kind {
+        LujvoPartKind::Cvc => {
+            for &c1 in &consonants {
+                for &v in &vowels {
+                    for &c2 in &consonants {
+                        rafsi.push([c1, v, c2].iter().collect());
+                    }
+                }
+            }
+        }
+        LujvoPartKind::Ccv => {
+            for initial in VALID_CC_INITIALS {
+                for &v in &vowels {
+                    rafsi.push(format!("{}{}", initial, v));
+                }
+            }
+        }
+        LujvoPartKind::Cvv => {
+            for &c in &consonants {
+                for diphthong in CVV_DIPHTHONGS {
+                    rafsi.push(format!("{}{}", c, diphthong));
+                }
+            }
+        }
+        LujvoPartKind::CvApostropheV => {
+            for &c in &consonants {
+                for &v1 in &vowels {
+                    for &v2 in &vowels {
+                        rafsi.push([c, v1, '\'', v2].iter().collect());
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+    rafsi
+}
+
+/// Short rafsi that no word in `source` owns yet
+///
+/// # Returns
+/// The free rafsi matching `filter`, by shape in the order of `filter.shapes`, then alphabetically
+pub fn free_rafsi(source: &(impl RafsiSource + ?Sized), filter: &FreeRafsiFilter) -> Vec<String> {
+    let source = Indexed::new(source);
+    let mut free = Vec::new();
+    for &kind in &filter.shapes {
+        let mut rafsi: Vec<String> = possible_rafsi(kind)
+            .into_iter()
+            .filter(|r| filter.matches(r, kind))
+            .filter(|r| source.selrafsi_candidates(r).is_empty())
+            .collect();
+        rafsi.sort();
+        free.append(&mut rafsi);
+    }
+    free
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvozba::dictionary::RafsiDictionary;
+
+    #[test]
+    fn test_possible_rafsi_counts() {
+        assert_eq!(possible_rafsi(LujvoPartKind::Cvc).len(), 17 * 5 * 17);
+        assert_eq!(possible_rafsi(LujvoPartKind::Ccv).len(), VALID_CC_INITIALS.len() * 5);
+        assert_eq!(possible_rafsi(LujvoPartKind::Cvv).len(), 17 * 4);
+        assert_eq!(possible_rafsi(LujvoPartKind::CvApostropheV).len(), 17 * 25);
+        for kind in SHORT_RAFSI_SHAPES {
+            assert!(possible_rafsi(kind)
+                .iter()
+                .all(|r| LujvoPartKind::from_rafsi(r) == Some(kind) && is_well_formed_short_rafsi(r)));
+        }
+        for rafsi in ["kua", "tla", "klam", "zbasu"] {
+            assert!(!is_well_formed_short_rafsi(rafsi), "{}", rafsi);
+        }
+    }
+
+    #[test]
+    fn test_free_rafsi() {
+        let dictionary = RafsiDictionary::with_experimental();
+        let free = free_rafsi(&dictionary, &FreeRafsiFilter::default());
+        assert!(!free.iter().any(|r| r == "kla" || r == "gau" || r == "nar" || r == "zvi"));
+        assert!(free.iter().all(|r| dictionary.selrafsi_candidates(r).is_empty()));
+
+        let filter = FreeRafsiFilter {
+            shapes: vec![LujvoPartKind::Ccv],
+            pattern: Some("kl?".to_string()),
+        };
+        let free_kl = free_rafsi(&dictionary, &filter);
+        assert!(free_kl.iter().all(|r| r.starts_with("kl") && r.len() == 3));
+        assert!(!free_kl.contains(&"kla".to_string()));
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use super::jvokaha::LujvoPartKind;
+
+/// Rafsi written as `-xyz-` in the notes of a jbovlaste entry
+static NOTES_RAFSI: Lazy<Regex> = Lazy::new(|| Regex::new(r"-([a-z']{3,4})-").unwrap());
+
+/// Rafsi maps and gismu list built from a jbovlaste XML export
+///
+/// The maps have the same layout as the bundled JSON files and can be passed as
+/// custom maps in [`RafsiOptions`](super::tools::RafsiOptions).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JbovlasteData {
+    /// Rafsi of every gismu and experimental gismu; gismu without rafsi map to an empty list
+    pub gismu_rafsi: HashMap<String, Vec<String>>,
+    /// Experimental rafsi proposed for gismu
+    pub gismu_rafsi_exp: HashMap<String, Vec<String>>,
+    /// Rafsi of cmavo that have any
+    pub cmavo_rafsi: HashMap<String, Vec<String>>,
+    /// Experimental rafsi proposed for cmavo
+    pub cmavo_rafsi_exp: HashMap<String, Vec<String>>,
+    /// Official gismu, sorted
+    pub gismu_list: Vec<String>,
+}
+
+/// Error reading a jbovlaste export
+#[derive(Debug)]
+pub enum ImportError {
+    Io(io::Error),
+    Xml(roxmltree::Error),
+    /// A `<valsi>` element without a `word` attribute
+    MissingWord { line: u32 },
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Io(e) => write!(f, "Failed to read jbovlaste export: {}", e),
+            ImportError::Xml(e) => write!(f, "Invalid jbovlaste XML: {}", e),
+            ImportError::MissingWord { line } => {
+                write!(f, "valsi without a word attribute at line {}", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImportError::Io(e) => Some(e),
+            ImportError::Xml(e) => Some(e),
+            ImportError::MissingWord { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImportError {
+    fn from(e: io::Error) -> Self {
+        ImportError::Io(e)
+    }
+}
+
+impl From<roxmltree::Error> for ImportError {
+    fn from(e: roxmltree::Error) -> Self {
+        ImportError::Xml(e)
+    }
+}
+
+/// Read a jbovlaste XML export from disk
+pub fn import_jbovlaste(path: &Path) -> Result<JbovlasteData, ImportError> {
+    parse_jbovlaste(&fs::read_to_string(path)?)
+}
+
+/// Build rafsi maps and the gismu list from the text of a jbovlaste XML export
+///
+/// Rafsi given as `<rafsi>` elements of gismu and cmavo are official. Rafsi of
+/// experimental words, and rafsi written as `-xyz-` in notes that mention rafsi,
+/// are experimental.
+pub fn parse_jbovlaste(xml: &str) -> Result<JbovlasteData, ImportError> {
+    let doc = roxmltree::Document::parse(xml)?;
+    let mut data = JbovlasteData::default();
+
+    for valsi in doc.descendants().filter(|n| n.has_tag_name("valsi")) {
+        let word = valsi.attribute("word").ok_or_else(|| ImportError::MissingWord {
+            line: doc.text_pos_at(valsi.range().start).row,
+        })?;
+        let (is_gismu, experimental) = match valsi.attribute("type").unwrap_or_default() {
+            "gismu" => (true, false),
+            "experimental gismu" => (true, true),
+            "cmavo" => (false, false),
+            "experimental cmavo" => (false, true),
+            _ => continue,
+        };
+
+        let rafsi: Vec<String> = valsi
+            .children()
+            .filter(|n| n.has_tag_name("rafsi"))
+            .filter_map(|n| n.text())
+            .map(|text| text.trim().to_string())
+            .filter(|text| !text.is_empty())
+            .collect();
+        let mut proposed: Vec<String> = Vec::new();
+        for r in valsi
+            .children()
+            .filter(|n| n.has_tag_name("notes"))
+            .filter_map(|n| n.text())
+            .filter(|notes| notes.to_lowercase().contains("rafsi"))
+            .flat_map(|notes| NOTES_RAFSI.captures_iter(notes))
+            .map(|c| c[1].to_string())
+            .filter(|r| LujvoPartKind::from_rafsi(r).is_some_and(|k| k.is_short_rafsi()))
+        {
+            if !rafsi.contains(&r) && !proposed.contains(&r) {
+                proposed.push(r);
+            }
+        }
+
+        let (official_map, exp_map) = if is_gismu {
+            (&mut data.gismu_rafsi, &mut data.gismu_rafsi_exp)
+        } else {
+            (&mut data.cmavo_rafsi, &mut data.cmavo_rafsi_exp)
+        };
+        if experimental {
+            proposed.splice(0..0, rafsi);
+            if is_gismu {
+                official_map.insert(word.to_string(), Vec::new());
+            }
+        } else {
+            if is_gismu {
+                data.gismu_list.push(word.to_string());
+            }
+            if is_gismu || !rafsi.is_empty() {
+                official_map.insert(word.to_string(), rafsi);
+            }
+        }
+        if !proposed.is_empty() {
+            exp_map.insert(word.to_string(), proposed);
+        }
+    }
+
+    data.gismu_list.sort();
+    Ok(data)
+}
+
+impl JbovlasteData {
+    /// Write the data in the layout of the source tree: the rafsi maps as JSON
+    /// files in `src_dir/jvozba`, and the gismu list as `src_dir/gismu-list.txt`
+    pub fn write_to(&self, src_dir: &Path) -> io::Result<()> {
+        let jvozba_dir = src_dir.join("jvozba");
+        for (name, map) in [
+            ("gismu_rafsi_list.json", &self.gismu_rafsi),
+            ("gismu_rafsi_list_exp.json", &self.gismu_rafsi_exp),
+            ("cmavo_rafsi_list.json", &self.cmavo_rafsi),
+            ("cmavo_rafsi_list_exp.json", &self.cmavo_rafsi_exp),
+        ] {
+            fs::write(jvozba_dir.join(name), rafsi_map_json(map)?)?;
+        }
+
+        let gismu_list: String = self.gismu_list.iter().map(|g| format!("{}\r\n", g)).collect();
+        fs::write(src_dir.join("gismu-list.txt"), gismu_list)
+    }
+}
+
+/// Serialize a rafsi map sorted by word and indented with tabs, like the bundled files
+fn rafsi_map_json(map: &HashMap<String, Vec<String>>) -> io::Result<String> {
+    let sorted: BTreeMap<&String, &Vec<String>> = map.iter().collect();
+    let mut out = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"\t");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+    serde::Serialize::serialize(&sorted, &mut serializer)?;
+    String::from_utf8(out).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<dictionary>
+<direction from="lojban" to="English">
+<valsi word="klama" type="gismu">
+    <rafsi>kla</rafsi>
+    <definition>$x_1$ comes/goes to $x_2$</definition>
+</valsi>
+<valsi word="badna" type="gismu">
+    <definition>$x_1$ is a banana</definition>
+</valsi>
+<valsi word="zviki" type="gismu">
+    <definition>$x_1$ is a wiki</definition>
+    <notes>Proposed rafsi: -zvi-.</notes>
+</valsi>
+<valsi unofficial="true" word="kibro" type="experimental gismu">
+    <definition>$x_1$ pertains to the internet</definition>
+    <notes>Proposed rafsi -kib-; see also -zvi-</notes>
+</valsi>
+<valsi word="na" type="cmavo">
+    <rafsi>nar</rafsi>
+    <selmaho>NA</selmaho>
+</valsi>
+<valsi word="ku" type="cmavo">
+    <selmaho>KU</selmaho>
+</valsi>
+<valsi unofficial="true" word="xo'ei" type="experimental cmavo">
+    <notes>rafsi: -xon-</notes>
+</valsi>
+<valsi word="klagau" type="lujvo">
+    <rafsi>xyz</rafsi>
+</valsi>
+</direction>
+</dictionary>
+"#;
+
+    fn map(entries: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.iter().map(|s| s.to_string()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_jbovlaste() {
+        let data = parse_jbovlaste(SAMPLE).unwrap();
+        assert_eq!(data.gismu_list, vec!["badna", "klama", "zviki"]);
+        assert_eq!(
+            data.gismu_rafsi,
+            map(&[("klama", &["kla"]), ("badna", &[]), ("zviki", &[]), ("kibro", &[])])
+        );
+        assert_eq!(
+            data.gismu_rafsi_exp,
+            map(&[("zviki", &["zvi"]), ("kibro", &["kib", "zvi"])])
+        );
+        assert_eq!(data.cmavo_rafsi, map(&[("na", &["nar"])]));
+        assert_eq!(data.cmavo_rafsi_exp, map(&[("xo'ei", &["xon"])]));
+    }
+
+    #[test]
+    fn test_parse_jbovlaste_errors() {
+        assert!(matches!(parse_jbovlaste("<dictionary>"), Err(ImportError::Xml(_))));
+        assert!(matches!(
+            parse_jbovlaste("<dictionary>\n<valsi type=\"gismu\"/>\n</dictionary>"),
+            Err(ImportError::MissingWord { line: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_rafsi_map_json_matches_bundled_layout() {
+        let json = rafsi_map_json(&map(&[("bacru", &["ba'u"]), ("backi", &[])])).unwrap();
+        assert_eq!(json, "{\n\t\"backi\": [],\n\t\"bacru\": [\n\t\t\"ba'u\"\n\t]\n}");
+    }
+}
@@ -1,30 +1,103 @@
+use crate::error::VlazbaError;
 use crate::jvozba::scoring::get_cv_info;
-use std::error::Error;
-use std::fmt;
+use serde::Serialize;
+use std::ops::Range;
 
+use super::dictionary::{Indexed, RafsiSource};
 use super::narge::normalize;
 
-#[derive(Debug)]
-struct LujvoError {
-    message: String,
+/// Split a lujvo into its constituent rafsi
+///
+/// # Arguments
+/// * `lujvo` - The compound word to analyze
+///
+/// # Returns
+/// Result with vector of rafsi, or the reason the word is not a valid lujvo
+pub fn jvokaha(lujvo: &str) -> Result<Vec<String>, VlazbaError> {
+    let arr = decompose_canonical(lujvo)?;
+    if is_slinkuhi(lujvo) {
+        return Err(VlazbaError::Slinkuhi {
+            lujvo: lujvo.to_string(),
+        });
+    }
+    Ok(arr)
 }
 
-impl fmt::Display for LujvoError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.message)
+/// Split a lujvo and look up the selrafsi of each of its rafsi
+///
+/// # Returns
+/// The best selrafsi for every rafsi, hyphens left out, or the reason the word is not a
+/// lujvo made of known rafsi
+pub fn jvokaha_selrafsi(
+    lujvo: &str,
+    options: &(impl RafsiSource + ?Sized),
+) -> Result<Vec<String>, VlazbaError> {
+    let options = Indexed::new(options);
+    let rafsi_list = jvokaha(lujvo)?;
+    let mut offset = 0;
+    let mut selrafsi_list = Vec::with_capacity(rafsi_list.len());
+    for rafsi in &rafsi_list {
+        if rafsi != "y" && rafsi != "r" && rafsi != "n" {
+            let candidate = options.selrafsi_candidates(rafsi).into_iter().next();
+            let candidate = candidate.ok_or_else(|| VlazbaError::UnknownRafsi {
+                rafsi: rafsi.clone(),
+                offset,
+            })?;
+            selrafsi_list.push(candidate.selrafsi);
+        }
+        offset += rafsi.len();
     }
+    Ok(selrafsi_list)
 }
 
-impl Error for LujvoError {}
+/// A lujvo split into rafsi, with the selrafsi of each
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LujvoDecomposition {
+    pub lujvo: String,
+    /// The rafsi of the lujvo, hyphens left out
+    pub rafsi: Vec<String>,
+    /// The best selrafsi of each rafsi, None for rafsi that no word has
+    pub selrafsi: Vec<Option<String>>,
+}
 
-/// Split a lujvo into its constituent rafsi
+/// Split a lujvo and look up the selrafsi of each of its rafsi, keeping unknown rafsi
 ///
-/// # Arguments
-/// * `lujvo` - The compound word to analyze
+/// Unlike [`jvokaha_selrafsi`], rafsi that no word has do not make this fail.
 ///
 /// # Returns
-/// Result with vector of rafsi or error message
-pub fn jvokaha(lujvo: &str) -> Result<Vec<String>, Box<dyn Error>> {
+/// The decomposition, or the reason the word is not a valid lujvo
+pub fn jvokaha_decomposition(
+    lujvo: &str,
+    options: &(impl RafsiSource + ?Sized),
+) -> Result<LujvoDecomposition, VlazbaError> {
+    let options = Indexed::new(options);
+    let rafsi: Vec<String> = jvokaha(lujvo)?
+        .into_iter()
+        .filter(|r| r.len() > 1)
+        .collect();
+    let selrafsi = rafsi
+        .iter()
+        .map(|r| options.selrafsi_candidates(r).into_iter().next().map(|c| c.selrafsi))
+        .collect();
+    Ok(LujvoDecomposition {
+        lujvo: lujvo.to_string(),
+        rafsi,
+        selrafsi,
+    })
+}
+
+/// Check whether a word fails the slinku'i test
+///
+/// A word fails if putting a CV cmavo such as `pa` in front of it forms a valid
+/// lujvo, since `pa` followed by the word could then be heard as a single word.
+/// The classic example is `slinku'i`, because `paslinku'i` splits into
+/// `pas-lin-ku'i`.
+pub fn is_slinkuhi(word: &str) -> bool {
+    decompose_canonical(&format!("pa{}", word)).is_ok()
+}
+
+/// Split a lujvo into rafsi and check that it is in canonical form, without the slinku'i test
+fn decompose_canonical(lujvo: &str) -> Result<Vec<String>, VlazbaError> {
     let arr = decompose_into_rafsi(lujvo)?;
     let rafsi_list: Vec<String> = arr.iter().filter(|a| a.len() != 1).cloned().collect();
 
@@ -32,18 +105,146 @@ pub fn jvokaha(lujvo: &str) -> Result<Vec<String>, Box<dyn Error>> {
     if lujvo == correct_lujvo {
         Ok(arr)
     } else {
-        Err(Box::new(LujvoError {
-            message: format!(
-                "malformed lujvo {{{}}}; it should be {{{}}}",
-                lujvo, correct_lujvo
-            ),
-        }))
+        Err(VlazbaError::NonCanonical {
+            lujvo: lujvo.to_string(),
+            correct: correct_lujvo,
+        })
     }
 }
 
+/// Kind of a single part of a lujvo: a rafsi of some shape or a hyphen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LujvoPartKind {
+    /// Short rafsi such as `mam`
+    Cvc,
+    /// Short rafsi such as `kla`
+    Ccv,
+    /// Short rafsi such as `gau`
+    Cvv,
+    /// Short rafsi such as `ca'i`
+    CvApostropheV,
+    /// 4-letter rafsi of a CVCCV gismu, such as `gasn`
+    Cvcc,
+    /// 4-letter rafsi of a CCVCV gismu, such as `klam`
+    Ccvc,
+    /// Full CVCCV gismu, such as `gasnu`
+    Cvccv,
+    /// Full CCVCV gismu, such as `mlatu`
+    Ccvcv,
+    /// `y` hyphen
+    YHyphen,
+    /// `r` hyphen
+    RHyphen,
+    /// `n` hyphen
+    NHyphen,
+}
+
+impl LujvoPartKind {
+    /// Determine the kind of a rafsi or hyphen from its letters
+    pub fn from_rafsi(rafsi: &str) -> Option<Self> {
+        match rafsi {
+            "y" => return Some(LujvoPartKind::YHyphen),
+            "r" => return Some(LujvoPartKind::RHyphen),
+            "n" => return Some(LujvoPartKind::NHyphen),
+            _ => {}
+        }
+        if rafsi.chars().count() != get_cv_info(rafsi).chars().count() {
+            return None;
+        }
+        match get_cv_info(rafsi).as_str() {
+            "CVC" => Some(LujvoPartKind::Cvc),
+            "CCV" => Some(LujvoPartKind::Ccv),
+            "CVV" => Some(LujvoPartKind::Cvv),
+            "CV'V" => Some(LujvoPartKind::CvApostropheV),
+            "CVCC" => Some(LujvoPartKind::Cvcc),
+            "CCVC" => Some(LujvoPartKind::Ccvc),
+            "CVCCV" => Some(LujvoPartKind::Cvccv),
+            "CCVCV" => Some(LujvoPartKind::Ccvcv),
+            _ => None,
+        }
+    }
+
+    /// Consonant/vowel pattern of this kind, e.g. `CV'V`
+    pub fn shape(&self) -> &'static str {
+        match self {
+            LujvoPartKind::Cvc => "CVC",
+            LujvoPartKind::Ccv => "CCV",
+            LujvoPartKind::Cvv => "CVV",
+            LujvoPartKind::CvApostropheV => "CV'V",
+            LujvoPartKind::Cvcc => "CVCC",
+            LujvoPartKind::Ccvc => "CCVC",
+            LujvoPartKind::Cvccv => "CVCCV",
+            LujvoPartKind::Ccvcv => "CCVCV",
+            LujvoPartKind::YHyphen => "y",
+            LujvoPartKind::RHyphen => "r",
+            LujvoPartKind::NHyphen => "n",
+        }
+    }
+
+    pub fn is_hyphen(&self) -> bool {
+        matches!(
+            self,
+            LujvoPartKind::YHyphen | LujvoPartKind::RHyphen | LujvoPartKind::NHyphen
+        )
+    }
+
+    /// Whether this is a 3-letter (CVC, CCV, CVV) or CV'V rafsi
+    pub fn is_short_rafsi(&self) -> bool {
+        matches!(
+            self,
+            LujvoPartKind::Cvc
+                | LujvoPartKind::Ccv
+                | LujvoPartKind::Cvv
+                | LujvoPartKind::CvApostropheV
+        )
+    }
+
+    pub fn is_4letter_rafsi(&self) -> bool {
+        matches!(self, LujvoPartKind::Cvcc | LujvoPartKind::Ccvc)
+    }
+
+    pub fn is_gismu(&self) -> bool {
+        matches!(self, LujvoPartKind::Cvccv | LujvoPartKind::Ccvcv)
+    }
+}
+
+/// A rafsi or hyphen of a decomposed lujvo
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LujvoPart {
+    pub text: String,
+    pub kind: LujvoPartKind,
+    /// Byte range of the part in the original lujvo
+    pub span: Range<usize>,
+}
+
+/// Split a lujvo into typed rafsi and hyphens with their positions
+///
+/// # Arguments
+/// * `lujvo` - The compound word to analyze
+///
+/// # Returns
+/// Result with vector of LujvoPart, or the reason the word is not a valid lujvo
+pub fn jvokaha_typed(lujvo: &str) -> Result<Vec<LujvoPart>, VlazbaError> {
+    let mut offset = 0;
+    jvokaha(lujvo)?
+        .into_iter()
+        .map(|text| {
+            let kind = LujvoPartKind::from_rafsi(&text).ok_or_else(|| {
+                VlazbaError::NotDecomposable {
+                    lujvo: lujvo.to_string(),
+                    offset,
+                }
+            })?;
+            let span = offset..offset + text.len();
+            offset = span.end;
+            Ok(LujvoPart { text, kind, span })
+        })
+        .collect()
+}
+
 /// Internal decomposition implementation that returns raw rafsi including hyphens
 /// Returns Ok even if the lujvo needs normalization (validation is done in outer function)
-fn decompose_into_rafsi(lujvo: &str) -> Result<Vec<String>, Box<dyn Error>> {
+fn decompose_into_rafsi(lujvo: &str) -> Result<Vec<String>, VlazbaError> {
     let original_lujvo = lujvo.to_string();
     let mut res: Vec<String> = Vec::new();
     let mut lujvo = lujvo.to_string();
@@ -53,8 +254,9 @@ fn decompose_into_rafsi(lujvo: &str) -> Result<Vec<String>, Box<dyn Error>> {
         if !res.is_empty()
             && res.last().unwrap().len() != 1
         {
-            let first_char = lujvo.chars().next().ok_or_else(|| LujvoError {
-                message: "Unexpected end of input".to_string(),
+            let first_char = lujvo.chars().next().ok_or_else(|| VlazbaError::NotDecomposable {
+                lujvo: original_lujvo.clone(),
+                offset: original_lujvo.len(),
             })?;
 
             let second_char = lujvo.chars().nth(1);
@@ -118,9 +320,10 @@ fn decompose_into_rafsi(lujvo: &str) -> Result<Vec<String>, Box<dyn Error>> {
             }
         }
 
-        return Err(Box::new(LujvoError {
-            message: format!("Failed to decompose {{{}}}", original_lujvo),
-        }));
+        return Err(VlazbaError::NotDecomposable {
+            offset: original_lujvo.len() - lujvo.len(),
+            lujvo: original_lujvo,
+        });
     }
 
     Ok(res)
@@ -157,12 +360,27 @@ mod tests {
 
     #[test]
     fn test_invalid_klasr() {
-        assert!(jvokaha("klasr").is_err());
+        assert_eq!(
+            jvokaha("klasr").unwrap_err(),
+            VlazbaError::NotDecomposable { lujvo: "klasr".to_string(), offset: 3 }
+        );
+    }
+
+    #[test]
+    fn test_non_canonical_lujvo() {
+        assert_eq!(
+            jvokaha("klamyseltru").ok(),
+            Some(vec!["klam".into(), "y".into(), "sel".into(), "tru".into()])
+        );
+        assert_eq!(
+            jvokaha("toibroda").unwrap_err(),
+            VlazbaError::NonCanonical { lujvo: "toibroda".to_string(), correct: "toirbroda".to_string() }
+        );
     }
 
     #[test]
     fn test_invalid_empty() {
-        assert!(jvokaha("").is_err());
+        assert_eq!(jvokaha("").unwrap_err(), VlazbaError::TooFewValsi { count: 0 });
     }
 
     #[test]
@@ -190,6 +408,53 @@ mod tests {
         assert!(jvokaha("klamrseltru").is_err());
     }
 
+    #[test]
+    fn test_jvokaha_typed() {
+        let parts = jvokaha_typed("klamyseltru").unwrap();
+        let kinds: Vec<LujvoPartKind> = parts.iter().map(|p| p.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                LujvoPartKind::Ccvc,
+                LujvoPartKind::YHyphen,
+                LujvoPartKind::Cvc,
+                LujvoPartKind::Ccv
+            ]
+        );
+        assert_eq!(parts[2].span, 5..8);
+        assert_eq!(&"klamyseltru"[parts[3].span.clone()], "tru");
+
+        let parts = jvokaha_typed("ca'irgau").unwrap();
+        assert_eq!(parts[0].kind, LujvoPartKind::CvApostropheV);
+        assert_eq!(parts[1].kind, LujvoPartKind::RHyphen);
+        assert_eq!(parts[2].span, 5..8);
+
+        assert_eq!(jvokaha_typed("bramlatu").unwrap()[1].kind, LujvoPartKind::Ccvcv);
+        assert!(jvokaha_typed("klasr").is_err());
+    }
+
+    #[test]
+    fn test_slinkuhi() {
+        assert!(is_slinkuhi("slinku'i"));
+        assert!(!is_slinkuhi("klagau"));
+        assert!(!is_slinkuhi("bramlatu"));
+    }
+
+    #[test]
+    fn test_jvokaha_selrafsi() {
+        use crate::jvozba::dictionary::RafsiDictionary;
+
+        let dictionary = RafsiDictionary::with_experimental();
+        assert_eq!(
+            jvokaha_selrafsi("gasnyzviki", &dictionary).unwrap(),
+            vec!["gasnu", "zviki"]
+        );
+        assert_eq!(
+            jvokaha_selrafsi("klazuvgau", &dictionary),
+            Err(VlazbaError::UnknownRafsi { rafsi: "zuv".to_string(), offset: 3 })
+        );
+    }
+
     #[test]
     fn test_jvokaha2_valid() {
         let result = decompose_into_rafsi("bramlatu").unwrap();
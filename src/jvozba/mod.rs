@@ -3,4 +3,11 @@ pub use narge::*;
 pub mod tools;
 pub mod scoring;
 pub mod rafsi_list;
-pub mod jvokaha;
\ No newline at end of file
+pub mod jvokaha;
+pub mod validate;
+pub mod jbovlaste;
+pub mod audit;
+pub mod rafsi_index;
+pub mod dictionary;
+pub mod inventory;
+pub mod recommend;
@@ -1,8 +1,20 @@
-use super::{scoring::get_lujvo_score, tools::{self, RafsiOptions}};
+use super::{
+    jvokaha::is_slinkuhi,
+    scoring::{get_lujvo_score, get_lujvo_score_breakdown, Cll1994, LujvoScoreBreakdown, LujvoScorer},
+    dictionary::RafsiSource,
+    tools,
+};
+use crate::error::VlazbaError;
+use crate::syllables::{syllabify, syllable_count, Syllable};
 use once_cell::sync::Lazy;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use serde::Serialize;
 use serde_json::Value;
-use std::collections::HashMap;
-use tools::{create_every_possibility, get_candid};
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+use std::time::{Duration, Instant};
+use tools::get_candid;
 
 static PERMISSIBILITY_TABLE: Lazy<HashMap<char, HashMap<char, i32>>> = Lazy::new(|| {
     let json: Value = serde_json::from_str(include_str!("permissible.json"))
@@ -24,7 +36,7 @@ static PERMISSIBILITY_TABLE: Lazy<HashMap<char, HashMap<char, i32>>> = Lazy::new
 });
 
 #[inline]
-fn is_permissible(c1: char, c2: char) -> i32 {
+pub(crate) fn is_permissible(c1: char, c2: char) -> i32 {
     PERMISSIBILITY_TABLE
         .get(&c1)
         .and_then(|row| row.get(&c2))
@@ -32,14 +44,126 @@ fn is_permissible(c1: char, c2: char) -> i32 {
         .unwrap_or(0)
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct LujvoAndScore {
     pub lujvo: String,
     pub score: i32,
+    /// [`LujvoScorer::name`] of the scorer that produced `score`
+    pub scorer: &'static str,
+    /// The rafsi and hyphens the lujvo is made of
+    pub rafsi: Vec<String>,
+    /// Terms of the CLL score, filled in by [`LujvoAndScore::explained`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breakdown: Option<LujvoScoreBreakdown>,
 }
 
+impl LujvoAndScore {
+    /// Score a normalized sequence of rafsi and hyphens with the CLL algorithm
+    pub fn from_rafsi(rafsi: Vec<String>) -> Self {
+        Self::scored_with(rafsi, &Cll1994)
+    }
+
+    /// Score a normalized sequence of rafsi and hyphens with `scorer`
+    pub fn scored_with(rafsi: Vec<String>, scorer: &(impl LujvoScorer + ?Sized)) -> Self {
+        LujvoAndScore {
+            lujvo: rafsi.join(""),
+            score: scorer.score(&rafsi),
+            scorer: scorer.name(),
+            rafsi,
+            breakdown: None,
+        }
+    }
+
+    /// Attach the breakdown of the CLL score into its terms
+    pub fn explained(mut self) -> Self {
+        self.breakdown = Some(get_lujvo_score_breakdown(&self.rafsi));
+        self
+    }
+
+    /// Syllables of the lujvo with the stressed one marked
+    pub fn syllables(&self) -> Vec<Syllable> {
+        syllabify(&self.lujvo)
+    }
+
+    pub fn syllable_count(&self) -> usize {
+        syllable_count(&self.lujvo)
+    }
+}
+
+/// Limits on the size of a jvozba search
+///
+/// The number of rafsi combinations grows exponentially with the number of
+/// components, so untrusted input should be searched with limits set.
+#[derive(Debug, Clone, Default)]
+pub struct JvozbaLimits {
+    /// Maximum number of selrafsi in the tanru
+    pub max_components: Option<usize>,
+    /// Maximum number of rafsi combinations to score
+    pub max_candidates: Option<u64>,
+    /// Maximum wall-clock time to spend on the search
+    pub timeout: Option<Duration>,
+}
+
+/// Error returned when a jvozba search exceeds its [`JvozbaLimits`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JvozbaError {
+    TooManyComponents {
+        components: usize,
+        limit: usize,
+        estimated_candidates: u128,
+    },
+    TooManyCandidates {
+        estimated_candidates: u128,
+        limit: u128,
+    },
+    Timeout {
+        limit: Duration,
+        estimated_candidates: u128,
+    },
+}
+
+impl JvozbaError {
+    /// Estimated number of rafsi combinations the search would have to score
+    pub fn estimated_candidates(&self) -> u128 {
+        match self {
+            JvozbaError::TooManyComponents { estimated_candidates, .. }
+            | JvozbaError::TooManyCandidates { estimated_candidates, .. }
+            | JvozbaError::Timeout { estimated_candidates, .. } => *estimated_candidates,
+        }
+    }
+}
+
+impl fmt::Display for JvozbaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JvozbaError::TooManyComponents { components, limit, estimated_candidates } => write!(
+                f,
+                "{} components exceed the limit of {} (about {} candidates)",
+                components, limit, estimated_candidates
+            ),
+            JvozbaError::TooManyCandidates { estimated_candidates, limit } => write!(
+                f,
+                "about {} candidates exceed the limit of {}",
+                estimated_candidates, limit
+            ),
+            JvozbaError::Timeout { limit, estimated_candidates } => write!(
+                f,
+                "search over about {} candidates did not finish within {:?}",
+                estimated_candidates, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for JvozbaError {}
+
 /// Generate possible lujvo combinations from a list of selrafsi
 ///
+/// This is the unlimited convenience wrapper around [`try_jvozba`]: it can take
+/// exponential time on long tanru, and every error, such as too few words or
+/// words without rafsi, gives an empty Vec. Use [`try_jvozba`] for untrusted input
+/// and to tell bad input from a tanru without lujvo.
+///
 /// # Arguments
 /// * `arr` - List of selrafsi (Lojban root words)
 /// * `forbid_la_lai_doi` - Whether to forbid certain cmavo in lujvo
@@ -51,27 +175,276 @@ pub fn jvozba(
     arr: &[String],
     forbid_la_lai_doi: bool,
     forbid_cmevla: bool,
-    options: &RafsiOptions,
+    options: &(impl RafsiSource + ?Sized),
 ) -> Vec<LujvoAndScore> {
+    try_jvozba(arr, forbid_la_lai_doi, forbid_cmevla, options, &Cll1994, &JvozbaLimits::default())
+        .unwrap_or_default()
+}
+
+/// Generate possible lujvo combinations ranked by `scorer`, giving up once the search
+/// exceeds `limits`
+///
+/// # Returns
+/// Vector of LujvoAndScore structs sorted by best score first, the words that have no
+/// rafsi, or the limit that was hit
+pub fn try_jvozba(
+    arr: &[String],
+    forbid_la_lai_doi: bool,
+    forbid_cmevla: bool,
+    options: &(impl RafsiSource + ?Sized),
+    scorer: &dyn LujvoScorer,
+    limits: &JvozbaLimits,
+) -> Result<Vec<LujvoAndScore>, VlazbaError> {
+    // Only read the clock with a timeout, as there may be none, e.g. in a browser
+    let started = limits.timeout.map(|_| Instant::now());
+    tools::check_selrafsi(arr, options)?;
     let candid_arr: Vec<Vec<String>> = arr
         .iter()
         .enumerate()
         .map(|(i, selrafsi)| get_candid(selrafsi, i == arr.len() - 1, options))
         .collect();
 
-    let mut answers: Vec<LujvoAndScore> = create_every_possibility(candid_arr)
-        .into_iter()
+    let estimated_candidates = candid_arr
+        .iter()
+        .fold(1u128, |acc, candid| acc.saturating_mul(candid.len() as u128));
+    if let Some(limit) = limits.max_components.filter(|&limit| arr.len() > limit) {
+        return Err(JvozbaError::TooManyComponents {
+            components: arr.len(),
+            limit,
+            estimated_candidates,
+        }
+        .into());
+    }
+    let candidate_limit = limits
+        .max_candidates
+        .map_or(usize::MAX as u128, |limit| (limit as u128).min(usize::MAX as u128));
+    if estimated_candidates > candidate_limit {
+        return Err(JvozbaError::TooManyCandidates {
+            estimated_candidates,
+            limit: candidate_limit,
+        }
+        .into());
+    }
+
+    #[cfg(feature = "parallel")]
+    let possibilities = tools::par_every_possibility(&candid_arr);
+    #[cfg(not(feature = "parallel"))]
+    let possibilities = tools::every_possibility(&candid_arr);
+    let mut answers: Vec<LujvoAndScore> = possibilities
         .filter_map(|rafsi_list| {
-            normalize(&rafsi_list).ok().map(|result| LujvoAndScore {
-                lujvo: result.join(""),
-                score: get_lujvo_score(&result),
+            if limits.timeout.zip(started).is_some_and(|(limit, started)| started.elapsed() > limit) {
+                return Some(Err(JvozbaError::Timeout {
+                    limit: limits.timeout.unwrap(),
+                    estimated_candidates,
+                }));
+            }
+            normalize(&rafsi_list)
+                .ok()
+                .map(|result| Ok(LujvoAndScore::scored_with(result, scorer)))
+        })
+        .filter(|d| {
+            d.as_ref().map_or(true, |d| {
+                !(is_forbidden(d, forbid_la_lai_doi)
+                    || forbid_cmevla && is_cmevla(&d.lujvo)
+                    || is_slinkuhi(&d.lujvo))
             })
         })
-        .filter(|d| !is_forbidden(d, forbid_la_lai_doi) && !(forbid_cmevla && is_cmevla(&d.lujvo)))
-        .collect();
+        .collect::<Result<_, JvozbaError>>()?;
 
     answers.sort_unstable_by_key(|a| a.score);
-    answers
+    Ok(answers)
+}
+
+/// Find the `k` best lujvo for a list of selrafsi without scoring every rafsi combination
+///
+/// Partial rafsi sequences are pruned with a lower bound on their final score: the
+/// score terms are sums over the rafsi, and hyphens only ever make a lujvo longer,
+/// so the hyphen-less score of the chosen rafsi plus the cheapest rafsi for every
+/// remaining position can never exceed the score of a completed lujvo. The answers
+/// have the same scores as the first `k` results of [`jvozba`]. Only the CLL score
+/// can be bounded this way, so other scorers need [`try_jvozba`].
+///
+/// Like [`jvozba`], this is the unlimited wrapper around [`try_jvozba_top_k`], and
+/// every error gives an empty Vec.
+///
+/// # Arguments
+/// * `arr` - List of selrafsi (Lojban root words)
+/// * `k` - Number of answers to return
+/// * `forbid_la_lai_doi` - Whether to forbid certain cmavo in lujvo
+/// * `forbid_cmevla` - Whether to forbid cmevla (name words)
+/// * `options` - Rafsi lists to use
+///
+/// # Returns
+/// Up to `k` LujvoAndScore structs sorted by best score first
+pub fn jvozba_top_k(
+    arr: &[String],
+    k: usize,
+    forbid_la_lai_doi: bool,
+    forbid_cmevla: bool,
+    options: &(impl RafsiSource + ?Sized),
+) -> Vec<LujvoAndScore> {
+    try_jvozba_top_k(arr, k, forbid_la_lai_doi, forbid_cmevla, options, &JvozbaLimits::default())
+        .unwrap_or_default()
+}
+
+/// Find the `k` best lujvo as [`jvozba_top_k`] does, giving up once the search exceeds
+/// `limits`
+///
+/// As most rafsi combinations are pruned, `max_candidates` limits the number of
+/// complete combinations scored rather than the number there are.
+///
+/// # Returns
+/// Up to `k` LujvoAndScore structs sorted by best score first, the words that have no
+/// rafsi, or the limit that was hit
+pub fn try_jvozba_top_k(
+    arr: &[String],
+    k: usize,
+    forbid_la_lai_doi: bool,
+    forbid_cmevla: bool,
+    options: &(impl RafsiSource + ?Sized),
+    limits: &JvozbaLimits,
+) -> Result<Vec<LujvoAndScore>, VlazbaError> {
+    let started = limits.timeout.map(|_| Instant::now());
+    tools::check_selrafsi(arr, options)?;
+    if arr.len() < 2 || k == 0 {
+        return Ok(Vec::new());
+    }
+
+    let candid_arr: Vec<Vec<(i32, String)>> = arr
+        .iter()
+        .enumerate()
+        .map(|(i, selrafsi)| {
+            let mut candid: Vec<(i32, String)> = get_candid(selrafsi, i == arr.len() - 1, options)
+                .into_iter()
+                .map(|rafsi| (get_lujvo_score(std::slice::from_ref(&rafsi)), rafsi))
+                .collect();
+            candid.sort_unstable();
+            candid
+        })
+        .collect();
+
+    let estimated_candidates = candid_arr
+        .iter()
+        .fold(1u128, |acc, candid| acc.saturating_mul(candid.len() as u128));
+    if let Some(limit) = limits.max_components.filter(|&limit| arr.len() > limit) {
+        return Err(JvozbaError::TooManyComponents {
+            components: arr.len(),
+            limit,
+            estimated_candidates,
+        }
+        .into());
+    }
+
+    // cheapest possible contribution of positions i.. to the final score
+    let mut suffix_bound = vec![0; candid_arr.len() + 1];
+    for (i, candid) in candid_arr.iter().enumerate().rev() {
+        match candid.first() {
+            Some((score, _)) => suffix_bound[i] = suffix_bound[i + 1] + score,
+            None => return Ok(Vec::new()),
+        }
+    }
+
+    let mut search = TopKSearch {
+        candid_arr: &candid_arr,
+        suffix_bound: &suffix_bound,
+        k,
+        forbid_la_lai_doi,
+        forbid_cmevla,
+        chosen: Vec::with_capacity(candid_arr.len()),
+        best: BinaryHeap::with_capacity(k + 1),
+        limits,
+        started,
+        estimated_candidates,
+        scored: 0,
+        exceeded: None,
+    };
+    search.descend(0);
+    if let Some(err) = search.exceeded {
+        return Err(err.into());
+    }
+
+    Ok(search
+        .best
+        .into_sorted_vec()
+        .into_iter()
+        .map(|(_, _, rafsi)| LujvoAndScore::from_rafsi(rafsi))
+        .collect())
+}
+
+struct TopKSearch<'a> {
+    candid_arr: &'a [Vec<(i32, String)>],
+    suffix_bound: &'a [i32],
+    k: usize,
+    forbid_la_lai_doi: bool,
+    forbid_cmevla: bool,
+    chosen: Vec<&'a str>,
+    best: BinaryHeap<(i32, String, Vec<String>)>,
+    limits: &'a JvozbaLimits,
+    started: Option<Instant>,
+    estimated_candidates: u128,
+    /// Complete rafsi combinations scored so far
+    scored: u64,
+    /// The limit the search stopped at
+    exceeded: Option<JvozbaError>,
+}
+
+impl<'a> TopKSearch<'a> {
+    fn descend(&mut self, partial_score: i32) {
+        if self.exceeded.is_some() {
+            return;
+        }
+        if let Some((limit, started)) = self.limits.timeout.zip(self.started) {
+            if started.elapsed() > limit {
+                self.exceeded = Some(JvozbaError::Timeout {
+                    limit,
+                    estimated_candidates: self.estimated_candidates,
+                });
+                return;
+            }
+        }
+        let depth = self.chosen.len();
+        if depth == self.candid_arr.len() {
+            self.accept();
+            return;
+        }
+
+        for (score, rafsi) in &self.candid_arr[depth] {
+            let bound = partial_score + score + self.suffix_bound[depth + 1];
+            if self.best.len() == self.k && self.best.peek().is_some_and(|(worst, ..)| bound >= *worst) {
+                // candidates are sorted by score, so the rest of this level is no better
+                break;
+            }
+            self.chosen.push(rafsi);
+            self.descend(partial_score + score);
+            self.chosen.pop();
+        }
+    }
+
+    fn accept(&mut self) {
+        self.scored += 1;
+        if let Some(limit) = self.limits.max_candidates.filter(|&limit| self.scored > limit) {
+            self.exceeded = Some(JvozbaError::TooManyCandidates {
+                estimated_candidates: self.estimated_candidates,
+                limit: limit as u128,
+            });
+            return;
+        }
+        let Ok(result) = normalize(&self.chosen) else {
+            return;
+        };
+        let answer = LujvoAndScore::from_rafsi(result);
+        if is_forbidden(&answer, self.forbid_la_lai_doi)
+            || self.forbid_cmevla && is_cmevla(&answer.lujvo)
+            || is_slinkuhi(&answer.lujvo)
+        {
+            return;
+        }
+
+        self.best.push((answer.score, answer.lujvo, answer.rafsi));
+        if self.best.len() > self.k {
+            self.best.pop();
+        }
+    }
 }
 
 #[inline]
@@ -89,19 +462,22 @@ fn is_forbidden(d: &LujvoAndScore, forbid_la_lai_doi: bool) -> bool {
 }
 
 #[inline]
-fn is_cmevla(valsi: &str) -> bool {
+pub(crate) fn is_cmevla(valsi: &str) -> bool {
     valsi.chars().last().is_some_and(is_c)
 }
 
-pub fn normalize(rafsi_list: &[String]) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+pub fn normalize<S: AsRef<str>>(rafsi_list: &[S]) -> Result<Vec<String>, VlazbaError> {
     if rafsi_list.len() < 2 {
-        return Err("You need at least two valsi to make a lujvo".into());
+        return Err(VlazbaError::TooFewValsi {
+            count: rafsi_list.len(),
+        });
     }
 
     let mut result: Vec<String> = Vec::with_capacity(rafsi_list.len() * 2 - 1);
-    result.push(rafsi_list.last().unwrap().clone());
+    result.push(rafsi_list.last().unwrap().as_ref().to_string());
 
     for (i, rafsi) in rafsi_list.iter().rev().skip(1).enumerate() {
+        let rafsi = rafsi.as_ref();
         let end = rafsi.chars().last().unwrap();
         let init = result[0].chars().next().unwrap();
 
@@ -125,7 +501,7 @@ pub fn normalize(rafsi_list: &[String]) -> Result<Vec<String>, Box<dyn std::erro
             result.insert(0, "y".to_string());
         }
 
-        result.insert(0, rafsi.clone());
+        result.insert(0, rafsi.to_string());
     }
 
     Ok(result)
@@ -191,7 +567,7 @@ fn is_4letter(rafsi: &str) -> bool {
 }
 
 #[inline]
-fn is_c(c: char) -> bool {
+pub(crate) fn is_c(c: char) -> bool {
     "bcdfgjklmnprstvxz".contains(c)
 }
 
@@ -211,6 +587,7 @@ fn get_cv_info(v: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::jvozba::tools::RafsiOptions;
 
     #[test]
     fn test_jvozba_klama_gasnu() {
@@ -229,6 +606,12 @@ mod tests {
             "jvozba should return at least one result"
         );
         assert_eq!(result[0].lujvo, "klagau", "First result should be 'klagau'");
+        assert_eq!(result[0].syllable_count(), 2);
+        assert_eq!(result[0].rafsi, vec!["kla", "gau"]);
+
+        let explained = result[0].clone().explained();
+        let breakdown = explained.breakdown.expect("explained result should carry a breakdown");
+        assert_eq!(breakdown.score, result[0].score);
     }
 
     #[test]
@@ -294,6 +677,174 @@ mod tests {
         assert!(result.iter().any(|r| r.lujvo == "qlagasnu"), "Expected custom rafsi combination");
     }
 
+    #[test]
+    fn test_jvozba_top_k_matches_exhaustive() {
+        let options = RafsiOptions {
+            exp_rafsi: true,
+            custom_cmavo: None,
+            custom_cmavo_exp: None,
+            custom_gismu: None,
+            custom_gismu_exp: None,
+        };
+        for tanru in [
+            "klama gasnu",
+            "bloti klama gasnu",
+            "ckule ctuca prenu nakni",
+            "barda mlatu na broda",
+        ] {
+            let input: Vec<String> = tanru.split_whitespace().map(String::from).collect();
+            let exhaustive = jvozba(&input, false, false, &options);
+            let top = jvozba_top_k(&input, 5, false, false, &options);
+            let expected: Vec<i32> = exhaustive.iter().take(5).map(|r| r.score).collect();
+            let actual: Vec<i32> = top.iter().map(|r| r.score).collect();
+            assert_eq!(actual, expected, "Scores should match for {}", tanru);
+            assert_eq!(top[0].lujvo, exhaustive[0].lujvo, "Best lujvo should match for {}", tanru);
+        }
+    }
+
+    #[test]
+    fn test_jvozba_top_k_long_tanru() {
+        let options = RafsiOptions {
+            exp_rafsi: false,
+            custom_cmavo: None,
+            custom_cmavo_exp: None,
+            custom_gismu: None,
+            custom_gismu_exp: None,
+        };
+        let input: Vec<String> = "barda xamgu ckule ctuca prenu nakni klama gasnu bloti mlatu"
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        let result = jvozba_top_k(&input, 3, false, false, &options);
+        assert_eq!(result.len(), 3);
+        assert!(result.windows(2).all(|w| w[0].score <= w[1].score));
+        assert!(jvozba_top_k(&input[..1], 3, false, false, &options).is_empty());
+    }
+
+    #[test]
+    fn test_try_jvozba_top_k() {
+        let options = RafsiOptions {
+            exp_rafsi: false,
+            custom_cmavo: None,
+            custom_cmavo_exp: None,
+            custom_gismu: None,
+            custom_gismu_exp: None,
+        };
+        let input: Vec<String> = "bloti klama gasnu".split_whitespace().map(String::from).collect();
+        let top = try_jvozba_top_k(&input, 3, false, false, &options, &JvozbaLimits::default()).unwrap();
+        let lujvo: Vec<&str> = top.iter().map(|r| r.lujvo.as_str()).collect();
+        let expected = jvozba_top_k(&input, 3, false, false, &options);
+        assert_eq!(lujvo, expected.iter().map(|r| r.lujvo.as_str()).collect::<Vec<_>>());
+
+        let unknown: Vec<String> = "foobar gasnu".split_whitespace().map(String::from).collect();
+        let err = try_jvozba_top_k(&unknown, 3, false, false, &options, &JvozbaLimits::default()).unwrap_err();
+        assert!(matches!(err, VlazbaError::InvalidSelrafsi(ref errors) if errors[0].word == "foobar"));
+
+        let limits = JvozbaLimits { max_components: Some(2), ..Default::default() };
+        let err = try_jvozba_top_k(&input, 3, false, false, &options, &limits).unwrap_err();
+        assert!(matches!(err, VlazbaError::SearchLimit(JvozbaError::TooManyComponents { components: 3, .. })));
+
+        let limits = JvozbaLimits { max_candidates: Some(1), ..Default::default() };
+        let err = try_jvozba_top_k(&input, 3, false, false, &options, &limits).unwrap_err();
+        assert!(matches!(err, VlazbaError::SearchLimit(JvozbaError::TooManyCandidates { limit: 1, .. })));
+
+        let limits = JvozbaLimits { timeout: Some(Duration::ZERO), ..Default::default() };
+        let err = try_jvozba_top_k(&input, 3, false, false, &options, &limits).unwrap_err();
+        assert!(matches!(err, VlazbaError::SearchLimit(JvozbaError::Timeout { .. })));
+    }
+
+    #[test]
+    fn test_try_jvozba_limits() {
+        let options = RafsiOptions {
+            exp_rafsi: false,
+            custom_cmavo: None,
+            custom_cmavo_exp: None,
+            custom_gismu: None,
+            custom_gismu_exp: None,
+        };
+        let input: Vec<String> = "bloti klama gasnu".split_whitespace().map(String::from).collect();
+
+        let limits = JvozbaLimits { max_components: Some(2), ..Default::default() };
+        let VlazbaError::SearchLimit(err) = try_jvozba(&input, false, false, &options, &Cll1994, &limits).unwrap_err() else {
+            panic!("Expected a search limit error");
+        };
+        assert!(matches!(err, JvozbaError::TooManyComponents { components: 3, limit: 2, .. }));
+        assert!(err.estimated_candidates() > 1);
+
+        let limits = JvozbaLimits { max_candidates: Some(2), ..Default::default() };
+        let err = try_jvozba(&input, false, false, &options, &Cll1994, &limits).unwrap_err();
+        assert!(matches!(err, VlazbaError::SearchLimit(JvozbaError::TooManyCandidates { limit: 2, .. })));
+
+        let limits = JvozbaLimits { timeout: Some(Duration::ZERO), ..Default::default() };
+        let err = try_jvozba(&input, false, false, &options, &Cll1994, &limits).unwrap_err();
+        assert!(matches!(err, VlazbaError::SearchLimit(JvozbaError::Timeout { .. })));
+
+        let limits = JvozbaLimits {
+            max_components: Some(3),
+            max_candidates: Some(1000),
+            timeout: Some(Duration::from_secs(60)),
+        };
+        let result = try_jvozba(&input, false, false, &options, &Cll1994, &limits).unwrap();
+        assert_eq!(result[0].lujvo, jvozba(&input, false, false, &options)[0].lujvo);
+    }
+
+    #[test]
+    fn test_try_jvozba_scorer() {
+        let options = RafsiOptions {
+            exp_rafsi: false,
+            custom_cmavo: None,
+            custom_cmavo_exp: None,
+            custom_gismu: None,
+            custom_gismu_exp: None,
+        };
+        let input: Vec<String> = "klama gasnu".split_whitespace().map(String::from).collect();
+        let scorer = crate::jvozba::scoring::SyllableCount;
+        let result = try_jvozba(&input, false, false, &options, &scorer, &JvozbaLimits::default()).unwrap();
+
+        assert!(result.iter().all(|r| r.scorer == "syllables"));
+        assert!(result.windows(2).all(|w| w[0].syllable_count() <= w[1].syllable_count()));
+        assert_eq!(result[0].lujvo, "klagau");
+        assert_eq!(jvozba(&input, false, false, &options)[0].scorer, "cll");
+    }
+
+    #[test]
+    fn test_try_jvozba_rejects_unknown_words() {
+        let options = RafsiOptions {
+            exp_rafsi: false,
+            custom_cmavo: None,
+            custom_cmavo_exp: None,
+            custom_gismu: None,
+            custom_gismu_exp: None,
+        };
+        let input: Vec<String> = "foobar gasnu".split_whitespace().map(String::from).collect();
+        let err = try_jvozba(&input, false, false, &options, &Cll1994, &JvozbaLimits::default()).unwrap_err();
+        assert!(matches!(err, VlazbaError::InvalidSelrafsi(ref errors) if errors[0].word == "foobar"));
+        assert!(jvozba(&input, false, false, &options).is_empty());
+        assert!(jvozba_top_k(&input, 3, false, false, &options).is_empty());
+    }
+
+    #[test]
+    fn test_jvozba_drops_slinkuhi() {
+        let mut custom_gismu = HashMap::new();
+        custom_gismu.insert("klama".into(), vec!["sli".into()]);
+        custom_gismu.insert("gasnu".into(), vec!["nku'i".into()]);
+
+        let input = vec!["klama".to_string(), "gasnu".to_string()];
+        let options = RafsiOptions {
+            exp_rafsi: false,
+            custom_cmavo: None,
+            custom_cmavo_exp: None,
+            custom_gismu: Some(&custom_gismu),
+            custom_gismu_exp: None,
+        };
+
+        let result = jvozba(&input, false, false, &options);
+        assert!(result.iter().any(|r| r.lujvo == "sligasnu"));
+        assert!(!result.iter().any(|r| r.lujvo == "slinku'i"), "slinku'i failure should be dropped");
+        let top = jvozba_top_k(&input, 1, false, false, &options);
+        assert_eq!(top[0].lujvo, result[0].lujvo);
+    }
+
     #[test]
     fn test_is_tosmabru() {
         // Test a valid tosmabru case
@@ -328,7 +879,7 @@ mod tests {
     fn test_normalize_error() {
         let input = vec!["klama".to_string()];
         let result = normalize(&input);
-        assert!(result.is_err(), "Normalizing single word should error");
+        assert_eq!(result, Err(VlazbaError::TooFewValsi { count: 1 }), "Normalizing single word should error");
     }
 
     #[test]
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+
+use super::jvokaha::LujvoPartKind;
+use super::rafsi_list::{
+    get_cmavo_rafsi_list, get_cmavo_rafsi_list_exp, get_gismu_rafsi_list, get_gismu_rafsi_list_exp,
+    RafsiList, RafsiMap,
+};
+use super::tools::RafsiOptions;
+
+static BUNDLED_INDEX: Lazy<RafsiIndex> = Lazy::new(|| RafsiIndex::new(&bundled_options(false)));
+static BUNDLED_INDEX_EXP: Lazy<RafsiIndex> = Lazy::new(|| RafsiIndex::new(&bundled_options(true)));
+
+/// Whether a rafsi assignment is official, experimental, or from a custom map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RafsiOrigin {
+    Official,
+    Experimental,
+    Custom,
+}
+
+/// A selrafsi that a rafsi may stand for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelrafsiCandidate {
+    pub selrafsi: String,
+    /// The map that assigns the rafsi
+    pub list: RafsiList,
+}
+
+impl SelrafsiCandidate {
+    pub fn origin(&self) -> RafsiOrigin {
+        if self.list.is_custom() {
+            RafsiOrigin::Custom
+        } else if self.list.is_experimental() {
+            RafsiOrigin::Experimental
+        } else {
+            RafsiOrigin::Official
+        }
+    }
+}
+
+/// Reverse index from rafsi to the selrafsi that own them
+///
+/// Covers the rafsi maps [`get_candid`](super::tools::get_candid) would use for
+/// the same options: custom maps replace the bundled map of the same kind, and
+/// experimental maps are only included with `exp_rafsi`. Besides the listed rafsi,
+/// every gismu also owns its 4-letter rafsi and itself.
+#[derive(Debug, Clone, Default)]
+pub struct RafsiIndex {
+    owners: HashMap<String, Vec<SelrafsiCandidate>>,
+}
+
+impl RafsiIndex {
+    pub fn new(options: &RafsiOptions) -> Self {
+        let mut index = RafsiIndex::default();
+        for (list, map) in effective_lists(options) {
+            for (selrafsi, rafsi_list) in map {
+                for rafsi in rafsi_list {
+                    index.insert(rafsi, selrafsi, list);
+                }
+                if !list.is_cmavo() {
+                    index.insert_gismu_forms(selrafsi, list);
+                }
+            }
+        }
+        index.sort();
+        index
+    }
+
+    /// Every selrafsi the rafsi may stand for, best first
+    ///
+    /// Non-experimental maps come before experimental ones, and gismu before cmavo.
+    /// Candidates from the same map are in alphabetical order.
+    pub fn candidates(&self, rafsi: &str) -> &[SelrafsiCandidate] {
+        self.owners.get(rafsi).map_or(&[], Vec::as_slice)
+    }
+
+    /// The best selrafsi for the rafsi
+    pub fn selrafsi(&self, rafsi: &str) -> Option<&str> {
+        self.candidates(rafsi).first().map(|c| c.selrafsi.as_str())
+    }
+
+    /// Add a rafsi unless the selrafsi already owns it
+    pub(crate) fn insert(&mut self, rafsi: &str, selrafsi: &str, list: RafsiList) {
+        let candidates = self.owners.entry(rafsi.to_string()).or_default();
+        if !candidates.iter().any(|c| c.selrafsi == selrafsi) {
+            candidates.push(SelrafsiCandidate {
+                selrafsi: selrafsi.to_string(),
+                list,
+            });
+        }
+    }
+
+    /// Add the 4-letter rafsi and the full form of a gismu
+    pub(crate) fn insert_gismu_forms(&mut self, gismu: &str, list: RafsiList) {
+        if !LujvoPartKind::from_rafsi(gismu).is_some_and(|k| k.is_gismu()) {
+            return;
+        }
+        let chopped = &gismu[..gismu.len() - 1];
+        if chopped != "brod" {
+            self.insert(chopped, gismu, list);
+        }
+        self.insert(gismu, gismu, list);
+    }
+
+    /// Put the candidates of every rafsi in priority order
+    pub(crate) fn sort(&mut self) {
+        for candidates in self.owners.values_mut() {
+            candidates.sort_by(|a, b| {
+                priority(a.list)
+                    .cmp(&priority(b.list))
+                    .then_with(|| a.selrafsi.cmp(&b.selrafsi))
+            });
+        }
+    }
+}
+
+/// Every selrafsi the rafsi may stand for, best first, as ordered by [`RafsiIndex::candidates`]
+///
+/// Without custom maps this uses a shared, precomputed index. With custom maps the
+/// index is built on every call, so build a [`RafsiIndex`] once for repeated lookups.
+pub fn find_selrafsi(rafsi: &str, options: &RafsiOptions) -> Vec<SelrafsiCandidate> {
+    if has_custom_maps(options) {
+        RafsiIndex::new(options).candidates(rafsi).to_vec()
+    } else {
+        bundled_index(options.exp_rafsi).candidates(rafsi).to_vec()
+    }
+}
+
+/// The shared index of the bundled rafsi maps
+pub fn bundled_index(exp_rafsi: bool) -> &'static RafsiIndex {
+    if exp_rafsi {
+        &BUNDLED_INDEX_EXP
+    } else {
+        &BUNDLED_INDEX
+    }
+}
+
+pub(crate) fn has_custom_maps(options: &RafsiOptions) -> bool {
+    options.custom_gismu.is_some()
+        || options.custom_gismu_exp.is_some()
+        || options.custom_cmavo.is_some()
+        || options.custom_cmavo_exp.is_some()
+}
+
+fn bundled_options(exp_rafsi: bool) -> RafsiOptions<'static> {
+    RafsiOptions {
+        exp_rafsi,
+        custom_cmavo: None,
+        custom_cmavo_exp: None,
+        custom_gismu: None,
+        custom_gismu_exp: None,
+    }
+}
+
+fn effective_lists<'a>(options: &RafsiOptions<'a>) -> Vec<(RafsiList, &'a RafsiMap)> {
+    let mut lists = vec![
+        options
+            .custom_gismu
+            .map_or((RafsiList::Gismu, get_gismu_rafsi_list()), |m| (RafsiList::CustomGismu, m)),
+        options
+            .custom_cmavo
+            .map_or((RafsiList::Cmavo, get_cmavo_rafsi_list()), |m| (RafsiList::CustomCmavo, m)),
+    ];
+    if options.exp_rafsi {
+        lists.push(
+            options
+                .custom_gismu_exp
+                .map_or((RafsiList::GismuExp, get_gismu_rafsi_list_exp()), |m| {
+                    (RafsiList::CustomGismuExp, m)
+                }),
+        );
+        lists.push(
+            options
+                .custom_cmavo_exp
+                .map_or((RafsiList::CmavoExp, get_cmavo_rafsi_list_exp()), |m| {
+                    (RafsiList::CustomCmavoExp, m)
+                }),
+        );
+    }
+    lists
+}
+
+fn priority(list: RafsiList) -> (bool, bool) {
+    (list.is_experimental(), list.is_cmavo())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_lookup() {
+        let index = bundled_index(false);
+        assert_eq!(index.selrafsi("kla"), Some("klama"));
+        assert_eq!(index.selrafsi("gasn"), Some("gasnu"));
+        assert_eq!(index.selrafsi("gasnu"), Some("gasnu"));
+        assert_eq!(index.selrafsi("nar"), Some("na"));
+        assert_eq!(index.selrafsi("brod"), None);
+        assert_eq!(index.selrafsi("zvi"), None);
+        assert_eq!(bundled_index(true).selrafsi("zvi"), Some("zviki"));
+
+        let candidates = find_selrafsi("kla", &bundled_options(true));
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].origin(), RafsiOrigin::Official);
+        assert_eq!(find_selrafsi("zvi", &bundled_options(true))[0].origin(), RafsiOrigin::Experimental);
+    }
+
+    #[test]
+    fn test_candidates_in_priority_order() {
+        let custom_cmavo: RafsiMap = [("xo'ei".to_string(), vec!["kla".to_string()])].into();
+        let custom_gismu_exp: RafsiMap = [
+            ("zbasu".to_string(), vec!["kla".to_string()]),
+            ("bisli".to_string(), vec!["kla".to_string()]),
+        ]
+        .into();
+        let options = RafsiOptions {
+            exp_rafsi: true,
+            custom_cmavo: Some(&custom_cmavo),
+            custom_cmavo_exp: None,
+            custom_gismu: None,
+            custom_gismu_exp: Some(&custom_gismu_exp),
+        };
+        let candidates: Vec<(String, RafsiOrigin)> = find_selrafsi("kla", &options)
+            .into_iter()
+            .map(|c| (c.selrafsi.clone(), c.origin()))
+            .collect();
+        assert_eq!(
+            candidates,
+            vec![
+                ("klama".to_string(), RafsiOrigin::Official),
+                ("xo'ei".to_string(), RafsiOrigin::Custom),
+                ("bisli".to_string(), RafsiOrigin::Custom),
+                ("zbasu".to_string(), RafsiOrigin::Custom),
+            ]
+        );
+        // custom maps replace the bundled map of the same kind
+        assert!(find_selrafsi("nar", &options).is_empty());
+    }
+}
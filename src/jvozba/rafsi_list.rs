@@ -2,7 +2,9 @@ use std::collections::HashMap;
 use once_cell::sync::Lazy;
 use serde_json;
 
-type RafsiMap = HashMap<String, Vec<String>>;
+use super::tools::RafsiOptions;
+
+pub type RafsiMap = HashMap<String, Vec<String>>;
 
 static GISMU_RAFSI_LIST: Lazy<RafsiMap> = Lazy::new(|| {
     serde_json::from_str(include_str!("gismu_rafsi_list.json"))
@@ -38,4 +40,77 @@ pub fn get_cmavo_rafsi_list() -> &'static RafsiMap {
 
 pub fn get_cmavo_rafsi_list_exp() -> &'static RafsiMap {
     &CMAVO_RAFSI_LIST_EXP
-}
\ No newline at end of file
+}
+
+/// One of the rafsi maps that rafsi can come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RafsiList {
+    Gismu,
+    GismuExp,
+    Cmavo,
+    CmavoExp,
+    CustomGismu,
+    CustomGismuExp,
+    CustomCmavo,
+    CustomCmavoExp,
+}
+
+impl RafsiList {
+    pub fn is_cmavo(&self) -> bool {
+        matches!(
+            self,
+            RafsiList::Cmavo | RafsiList::CmavoExp | RafsiList::CustomCmavo | RafsiList::CustomCmavoExp
+        )
+    }
+
+    pub fn is_experimental(&self) -> bool {
+        matches!(
+            self,
+            RafsiList::GismuExp | RafsiList::CmavoExp | RafsiList::CustomGismuExp | RafsiList::CustomCmavoExp
+        )
+    }
+
+    pub fn is_custom(&self) -> bool {
+        matches!(
+            self,
+            RafsiList::CustomGismu | RafsiList::CustomGismuExp | RafsiList::CustomCmavo | RafsiList::CustomCmavoExp
+        )
+    }
+}
+
+impl std::fmt::Display for RafsiList {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            RafsiList::Gismu => "gismu",
+            RafsiList::GismuExp => "experimental gismu",
+            RafsiList::Cmavo => "cmavo",
+            RafsiList::CmavoExp => "experimental cmavo",
+            RafsiList::CustomGismu => "custom gismu",
+            RafsiList::CustomGismuExp => "custom experimental gismu",
+            RafsiList::CustomCmavo => "custom cmavo",
+            RafsiList::CustomCmavoExp => "custom experimental cmavo",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The bundled rafsi maps followed by the custom maps set in `options`
+pub fn all_rafsi_lists<'a>(options: &RafsiOptions<'a>) -> Vec<(RafsiList, &'a RafsiMap)> {
+    let mut lists: Vec<(RafsiList, &'a RafsiMap)> = vec![
+        (RafsiList::Gismu, get_gismu_rafsi_list()),
+        (RafsiList::GismuExp, get_gismu_rafsi_list_exp()),
+        (RafsiList::Cmavo, get_cmavo_rafsi_list()),
+        (RafsiList::CmavoExp, get_cmavo_rafsi_list_exp()),
+    ];
+    for (list, map) in [
+        (RafsiList::CustomGismu, options.custom_gismu),
+        (RafsiList::CustomGismuExp, options.custom_gismu_exp),
+        (RafsiList::CustomCmavo, options.custom_cmavo),
+        (RafsiList::CustomCmavoExp, options.custom_cmavo_exp),
+    ] {
+        if let Some(map) = map {
+            lists.push((list, map));
+        }
+    }
+    lists
+}
@@ -0,0 +1,263 @@
+use super::dictionary::{Indexed, RafsiSource};
+use super::jvokaha::LujvoPartKind;
+use super::narge::{try_jvozba, JvozbaLimits};
+use super::scoring::Cll1994;
+use super::rafsi_index::SelrafsiCandidate;
+use crate::error::{SelrafsiError, SelrafsiProblem, VlazbaError};
+use crate::libs::config::VALID_CC_INITIALS;
+use crate::valsi::{classify_valsi, ValsiClass};
+
+/// Frequent lujvo components that [`recommend_rafsi`] tries new rafsi with by default
+pub const COMMON_PARTNERS: &[&str] = &[
+    "gasnu", "zbasu", "klama", "prenu", "zmadu", "mutce", "jbena", "cmene", "ckule", "tavla",
+    "citka", "pilno", "sutra", "barda", "cmalu", "nakni", "fetsi", "djedi", "zdani", "tadni",
+    "se", "na", "ka", "nu", "lo",
+];
+
+/// A rafsi that could be proposed for a gismu, with how much it would help
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RafsiRecommendation {
+    pub rafsi: String,
+    pub shape: LujvoPartKind,
+    /// Number of partner lujvo whose best score the rafsi would improve
+    pub improved: usize,
+    /// Sum of the score improvements over all partner lujvo
+    pub gain: i32,
+}
+
+/// Suggest new rafsi for a gismu, best first
+///
+/// Candidates are formed from the letters of the gismu by the CLL patterns:
+/// CVC from a consonant and the vowel after it with one of the consonants that follow,
+/// CCV from a consonant pair that can start a word, and CVV or CV'V from a consonant
+/// and the two vowels. As the short ones are mostly taken, the same patterns are also
+/// tried with the final vowel. Rafsi that some other word already has are dropped.
+///
+/// Each remaining rafsi is tried in two-part lujvo with every partner in `partners`,
+/// both before and after it, and ranked by how many of these lujvo it would give a
+/// better [`get_lujvo_score`](super::scoring::get_lujvo_score), then by the total gain.
+/// Only the score is compared, not the length; as the score counts 1000 per letter, a
+/// shorter lujvo nearly always scores better. Rafsi that improve no lujvo are kept, with
+/// `improved` 0, at the end.
+pub fn recommend_rafsi(
+    gismu: &str,
+    source: &(impl RafsiSource + ?Sized),
+    partners: &[&str],
+) -> Result<Vec<RafsiRecommendation>, VlazbaError> {
+    match classify_valsi(gismu) {
+        ValsiClass::Gismu { .. } => {}
+        class => {
+            return Err(VlazbaError::InvalidSelrafsi(vec![SelrafsiError {
+                word: gismu.to_string(),
+                position: 0,
+                problem: SelrafsiProblem::NotGismu(class),
+            }]))
+        }
+    }
+
+    let source = &Indexed::new(source);
+    let existing = source.gismu_rafsi(gismu);
+    let pairs: Vec<[String; 2]> = partners
+        .iter()
+        .filter(|&&partner| partner != gismu)
+        .flat_map(|&partner| {
+            [
+                [gismu.to_string(), partner.to_string()],
+                [partner.to_string(), gismu.to_string()],
+            ]
+        })
+        .collect();
+    let baseline: Vec<Option<i32>> = pairs.iter().map(|pair| best_score(pair, source)).collect();
+
+    let mut recommendations: Vec<RafsiRecommendation> = derive_rafsi(gismu)
+        .into_iter()
+        .filter(|(rafsi, _)| !existing.contains(rafsi))
+        .filter(|(rafsi, _)| {
+            source
+                .selrafsi_candidates(rafsi)
+                .iter()
+                .all(|c| c.selrafsi == gismu)
+        })
+        .map(|(rafsi, shape)| {
+            let with_rafsi = WithRafsi {
+                inner: source,
+                gismu,
+                rafsi: &rafsi,
+            };
+            let mut improved = 0;
+            let mut gain = 0;
+            for (pair, before) in pairs.iter().zip(&baseline) {
+                if let (Some(before), Some(after)) = (before, best_score(pair, &with_rafsi)) {
+                    if after < *before {
+                        improved += 1;
+                        gain += before - after;
+                    }
+                }
+            }
+            RafsiRecommendation {
+                rafsi,
+                shape,
+                improved,
+                gain,
+            }
+        })
+        .collect();
+
+    recommendations.sort_by(|a, b| {
+        b.improved
+            .cmp(&a.improved)
+            .then(b.gain.cmp(&a.gain))
+            .then_with(|| a.rafsi.cmp(&b.rafsi))
+    });
+    Ok(recommendations)
+}
+
+/// Format recommended rafsi as an entry of `gismu_rafsi_list_exp.json`
+pub fn exp_rafsi_entry(gismu: &str, recommendations: &[RafsiRecommendation]) -> String {
+    let rafsi: Vec<String> = recommendations
+        .iter()
+        .map(|r| format!("\t\t\"{}\"", r.rafsi))
+        .collect();
+    if rafsi.is_empty() {
+        format!("\t\"{}\": []", gismu)
+    } else {
+        format!("\t\"{}\": [\n{}\n\t]", gismu, rafsi.join(",\n"))
+    }
+}
+
+/// Short rafsi that can be formed from the letters of a gismu, without duplicates
+///
+/// The CLL patterns come first, then the same patterns with the final vowel of
+/// the gismu in place of the first.
+fn derive_rafsi(gismu: &str) -> Vec<(String, LujvoPartKind)> {
+    let letters: Vec<char> = gismu.chars().collect();
+    let patterns: &[[usize; 3]] = match LujvoPartKind::from_rafsi(gismu) {
+        // c1 v1 c2 c3 v2, e.g. gasnu
+        Some(LujvoPartKind::Cvccv) => &[
+            [0, 1, 2],
+            [0, 1, 3],
+            [2, 3, 4],
+            [0, 2, 1],
+            [0, 1, 4],
+            [0, 4, 2],
+            [0, 4, 3],
+            [2, 3, 1],
+            [0, 2, 4],
+        ],
+        // c1 c2 v1 c3 v2, e.g. klama
+        Some(LujvoPartKind::Ccvcv) => &[
+            [0, 1, 2],
+            [0, 2, 3],
+            [1, 2, 3],
+            [0, 2, 4],
+            [1, 2, 4],
+            [0, 1, 4],
+            [0, 4, 3],
+            [1, 4, 3],
+        ],
+        _ => &[],
+    };
+
+    let mut rafsi: Vec<(String, LujvoPartKind)> = Vec::new();
+    let mut push = |text: String, kind: LujvoPartKind| {
+        if !rafsi.iter().any(|(r, _)| *r == text) {
+            rafsi.push((text, kind));
+        }
+    };
+    for pattern in patterns {
+        let text: String = pattern.iter().map(|&i| letters[i]).collect();
+        match LujvoPartKind::from_rafsi(&text) {
+            Some(LujvoPartKind::Ccv) if !VALID_CC_INITIALS.contains(&&text[..2]) => {}
+            Some(LujvoPartKind::Cvv) => {
+                if matches!(&text[1..], "ai" | "ei" | "oi" | "au") {
+                    push(text.clone(), LujvoPartKind::Cvv);
+                }
+                let apostrophe = format!("{}'{}", &text[..2], &text[2..]);
+                push(apostrophe, LujvoPartKind::CvApostropheV);
+            }
+            Some(kind) => push(text, kind),
+            None => {}
+        }
+    }
+    rafsi
+}
+
+/// The best score of a two-part lujvo, if it can be made
+fn best_score(pair: &[String; 2], source: &(impl RafsiSource + ?Sized)) -> Option<i32> {
+    try_jvozba(pair, false, false, source, &Cll1994, &JvozbaLimits::default())
+        .ok()?
+        .first()
+        .map(|r| r.score)
+}
+
+/// A rafsi source with one extra rafsi for one gismu
+struct WithRafsi<'a, S: ?Sized> {
+    inner: &'a S,
+    gismu: &'a str,
+    rafsi: &'a str,
+}
+
+impl<S: RafsiSource + ?Sized> RafsiSource for WithRafsi<'_, S> {
+    fn cmavo_rafsi(&self, cmavo: &str) -> Option<Vec<String>> {
+        self.inner.cmavo_rafsi(cmavo)
+    }
+
+    fn gismu_rafsi(&self, gismu: &str) -> Vec<String> {
+        let mut rafsi = self.inner.gismu_rafsi(gismu);
+        if gismu == self.gismu {
+            rafsi.push(self.rafsi.to_string());
+        }
+        rafsi
+    }
+
+    fn selrafsi_candidates(&self, rafsi: &str) -> Vec<SelrafsiCandidate> {
+        self.inner.selrafsi_candidates(rafsi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvozba::dictionary::RafsiDictionary;
+
+    #[test]
+    fn test_derive_rafsi() {
+        let baktu: Vec<String> = derive_rafsi("baktu").into_iter().map(|(r, _)| r).collect();
+        assert_eq!(baktu, vec!["bak", "bat", "bau", "ba'u", "buk", "but"]);
+        let klama: Vec<String> = derive_rafsi("klama").into_iter().map(|(r, _)| r).collect();
+        assert_eq!(klama, vec!["kla", "kam", "lam", "ka'a", "la'a"]);
+        assert!(derive_rafsi("valsi").iter().any(|(r, _)| r == "vla"));
+        assert!(derive_rafsi("badri").iter().any(|(r, _)| r == "dri"));
+    }
+
+    #[test]
+    fn test_recommend_rafsi() {
+        let dictionary = RafsiDictionary::with_experimental();
+        let recommendations = recommend_rafsi("baktu", &dictionary, COMMON_PARTNERS).unwrap();
+        assert!(recommendations.iter().any(|r| r.rafsi == "but"));
+        for r in &recommendations {
+            assert!(dictionary.selrafsi_candidates(&r.rafsi).is_empty());
+        }
+        assert!(recommendations.windows(2).all(|w| w[0].improved >= w[1].improved));
+
+        assert!(matches!(
+            recommend_rafsi("foobar", &dictionary, COMMON_PARTNERS),
+            Err(VlazbaError::InvalidSelrafsi(_))
+        ));
+    }
+
+    #[test]
+    fn test_exp_rafsi_entry() {
+        let recommendation = |rafsi: &str| RafsiRecommendation {
+            rafsi: rafsi.to_string(),
+            shape: LujvoPartKind::Cvc,
+            improved: 1,
+            gain: 1,
+        };
+        assert_eq!(
+            exp_rafsi_entry("baktu", &[recommendation("bat"), recommendation("bau")]),
+            "\t\"baktu\": [\n\t\t\"bat\",\n\t\t\"bau\"\n\t]"
+        );
+        assert_eq!(exp_rafsi_entry("baktu", &[]), "\t\"baktu\": []");
+    }
+}
@@ -1,28 +1,162 @@
+use serde::Serialize;
+use std::fmt;
+
+/// The lujvo score `1000L - 500A + 100H - 10R - V` (CLL 4.12); see [`get_lujvo_score_breakdown`] for its terms
 pub fn get_lujvo_score(rafsi_ynr_sequence: &[String]) -> i32 {
-    let lujvo = rafsi_ynr_sequence.join("");
-    let l = lujvo.len() as i32;
-    let a = lujvo.matches('\'').count() as i32;
-    let mut h = 0;
-    let mut r = 0;
-
-    for rafsi in rafsi_ynr_sequence {
-        match get_cv_info(rafsi).as_str() {
-            "C" | "Y" => h += 1, // ynr-hyphen
-            "CVCCV" => r += 1,
-            "CVCC" => r += 2,
-            "CCVCV" => r += 3,
-            "CCVC" => r += 4,
-            "CVC" => r += 5,
-            "CV'V" => r += 6,
-            "CCV" => r += 7,
-            "CVV" => r += 8,
-            _ => {}
+    ScoreTerms::of(rafsi_ynr_sequence).score()
+}
+
+/// Contribution of a rafsi to the R term of the lujvo score, by its consonant/vowel pattern
+fn rafsi_shape_value(cv_info: &[u8]) -> i32 {
+    match cv_info {
+        b"CVCCV" => 1,
+        b"CVCC" => 2,
+        b"CCVCV" => 3,
+        b"CCVC" => 4,
+        b"CVC" => 5,
+        b"CV'V" => 6,
+        b"CCV" => 7,
+        b"CVV" => 8,
+        _ => 0,
+    }
+}
+
+/// What a part of a rafsi/hyphen sequence adds to the lujvo score
+enum PartTerm {
+    /// A y, r or n hyphen, counted in H
+    Hyphen,
+    /// A rafsi and its shape value, counted in R
+    Rafsi(i32),
+}
+
+/// Classify a part by its consonant/vowel pattern, as [`get_cv_info`] gives it, without allocating
+fn part_term(part: &str) -> PartTerm {
+    let mut cv_info = [0u8; 5];
+    let mut len = 0;
+    for c in part.chars() {
+        let symbol = match c {
+            'a' | 'e' | 'i' | 'o' | 'u' => b'V',
+            'b' | 'c' | 'd' | 'f' | 'g' | 'j' | 'k' | 'l' | 'm' | 'n' | 'p' | 'r' | 's' | 't' | 'v' | 'x' | 'z' => b'C',
+            '\'' => b'\'',
+            'y' => b'Y',
+            _ => continue,
+        };
+        if len == cv_info.len() {
+            // Longer than any rafsi shape
+            return PartTerm::Rafsi(0);
+        }
+        cv_info[len] = symbol;
+        len += 1;
+    }
+    match &cv_info[..len] {
+        b"C" | b"Y" => PartTerm::Hyphen,
+        cv_info => PartTerm::Rafsi(rafsi_shape_value(cv_info)),
+    }
+}
+
+/// The terms of the lujvo score without the contribution of each rafsi
+struct ScoreTerms {
+    letters: i32,
+    apostrophes: i32,
+    hyphens: i32,
+    rafsi_value: i32,
+    vowels: i32,
+}
+
+impl ScoreTerms {
+    fn of(rafsi_ynr_sequence: &[String]) -> Self {
+        let mut terms = ScoreTerms { letters: 0, apostrophes: 0, hyphens: 0, rafsi_value: 0, vowels: 0 };
+        for part in rafsi_ynr_sequence {
+            terms.letters += part.len() as i32;
+            for c in part.chars() {
+                match c {
+                    '\'' => terms.apostrophes += 1,
+                    'a' | 'e' | 'i' | 'o' | 'u' => terms.vowels += 1,
+                    _ => {}
+                }
+            }
+            match part_term(part) {
+                PartTerm::Hyphen => terms.hyphens += 1,
+                PartTerm::Rafsi(value) => terms.rafsi_value += value,
+            }
         }
+        terms
     }
 
-    let v = lujvo.chars().filter(|&c| "aeiou".contains(c)).count() as i32;
-    
-    (1000 * l) - (500 * a) + (100 * h) - (10 * r) - v
+    fn score(&self) -> i32 {
+        (1000 * self.letters) - (500 * self.apostrophes) + (100 * self.hyphens) - (10 * self.rafsi_value) - self.vowels
+    }
+}
+
+/// The R term contribution of a single rafsi
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RafsiContribution {
+    pub rafsi: String,
+    /// Consonant/vowel pattern of the rafsi, e.g. `CCV`
+    pub shape: String,
+    pub value: i32,
+}
+
+/// Every term of the lujvo score `1000L - 500A + 100H - 10R - V` (CLL 4.12)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LujvoScoreBreakdown {
+    /// L: number of letters, counting apostrophes and hyphens
+    pub letters: i32,
+    /// A: number of apostrophes
+    pub apostrophes: i32,
+    /// H: number of y, r and n hyphens
+    pub hyphens: i32,
+    /// R: sum of the rafsi shape values
+    pub rafsi_value: i32,
+    /// V: number of vowels, not counting y
+    pub vowels: i32,
+    /// R contribution of each rafsi, in order
+    pub rafsi: Vec<RafsiContribution>,
+    pub score: i32,
+}
+
+/// Compute the lujvo score, keeping every term
+///
+/// This allocates for each rafsi; use [`get_lujvo_score`] when only the score is needed.
+pub fn get_lujvo_score_breakdown(rafsi_ynr_sequence: &[String]) -> LujvoScoreBreakdown {
+    let terms = ScoreTerms::of(rafsi_ynr_sequence);
+    let rafsi = rafsi_ynr_sequence
+        .iter()
+        .filter_map(|part| match part_term(part) {
+            PartTerm::Hyphen => None,
+            PartTerm::Rafsi(value) => Some(RafsiContribution {
+                rafsi: part.clone(),
+                shape: get_cv_info(part),
+                value,
+            }),
+        })
+        .collect();
+
+    LujvoScoreBreakdown {
+        letters: terms.letters,
+        apostrophes: terms.apostrophes,
+        hyphens: terms.hyphens,
+        rafsi_value: terms.rafsi_value,
+        vowels: terms.vowels,
+        rafsi,
+        score: terms.score(),
+    }
+}
+
+impl fmt::Display for LujvoScoreBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "1000*L({}) - 500*A({}) + 100*H({}) - 10*R({}) - V({}) = {}",
+            self.letters, self.apostrophes, self.hyphens, self.rafsi_value, self.vowels, self.score
+        )?;
+        let parts: Vec<String> = self
+            .rafsi
+            .iter()
+            .map(|r| format!("{} {} = {}", r.rafsi, r.shape, r.value))
+            .collect();
+        write!(f, "R: {}", parts.join(", "))
+    }
 }
 
 pub fn get_cv_info(v: &str) -> String {
@@ -36,3 +170,190 @@ pub fn get_cv_info(v: &str) -> String {
         })
         .collect()
 }
+
+/// A way of ranking lujvo; lower scores are better
+///
+/// Scorers get the normalized sequence of rafsi and hyphens that makes up a lujvo.
+pub trait LujvoScorer: Sync {
+    /// Short name used to select the scorer, e.g. on the command line
+    fn name(&self) -> &'static str;
+
+    fn score(&self, rafsi_ynr_sequence: &[String]) -> i32;
+}
+
+/// The scoring algorithm of CLL 4.12 (1994), as in [`get_lujvo_score`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cll1994;
+
+impl LujvoScorer for Cll1994 {
+    fn name(&self) -> &'static str {
+        "cll"
+    }
+
+    fn score(&self, rafsi_ynr_sequence: &[String]) -> i32 {
+        get_lujvo_score(rafsi_ynr_sequence)
+    }
+}
+
+/// Fewest letters first, ties broken by the CLL score
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShortestLetters;
+
+impl LujvoScorer for ShortestLetters {
+    fn name(&self) -> &'static str {
+        "letters"
+    }
+
+    fn score(&self, rafsi_ynr_sequence: &[String]) -> i32 {
+        let letters: usize = rafsi_ynr_sequence
+            .iter()
+            .map(|r| r.chars().filter(|&c| c != '\'').count())
+            .sum();
+        (letters as i32)
+            .saturating_mul(1_000_000)
+            .saturating_add(get_lujvo_score(rafsi_ynr_sequence))
+    }
+}
+
+/// Fewest syllables first, ties broken by the CLL score
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SyllableCount;
+
+impl LujvoScorer for SyllableCount {
+    fn name(&self) -> &'static str {
+        "syllables"
+    }
+
+    fn score(&self, rafsi_ynr_sequence: &[String]) -> i32 {
+        let syllables = crate::syllables::syllable_count(&rafsi_ynr_sequence.join(""));
+        (syllables as i32)
+            .saturating_mul(1_000_000)
+            .saturating_add(get_lujvo_score(rafsi_ynr_sequence))
+    }
+}
+
+/// CLL-style scoring with separate, adjustable costs for hyphens and apostrophes
+///
+/// Letters cost 1000 and rafsi shapes and vowels count as in CLL, but hyphens and
+/// apostrophes are not counted as letters; they cost `y_hyphen`, `rn_hyphen` and
+/// `apostrophe` instead. By default a y-hyphen costs as much as two letters, since
+/// it adds a syllable, and r/n-hyphens and apostrophes as much as one letter.
+#[derive(Debug, Clone, Copy)]
+pub struct HyphenPenalty {
+    pub y_hyphen: i32,
+    pub rn_hyphen: i32,
+    pub apostrophe: i32,
+}
+
+impl Default for HyphenPenalty {
+    fn default() -> Self {
+        HyphenPenalty {
+            y_hyphen: 2000,
+            rn_hyphen: 1000,
+            apostrophe: 1000,
+        }
+    }
+}
+
+impl LujvoScorer for HyphenPenalty {
+    fn name(&self) -> &'static str {
+        "penalty"
+    }
+
+    fn score(&self, rafsi_ynr_sequence: &[String]) -> i32 {
+        let mut score = 0;
+        for rafsi in rafsi_ynr_sequence {
+            match get_cv_info(rafsi).as_str() {
+                "Y" => score += self.y_hyphen,
+                "C" => score += self.rn_hyphen,
+                cv_info => {
+                    let apostrophes = rafsi.matches('\'').count() as i32;
+                    let vowels = rafsi.chars().filter(|&c| "aeiou".contains(c)).count() as i32;
+                    score += 1000 * (rafsi.len() as i32 - apostrophes) + self.apostrophe * apostrophes
+                        - 10 * rafsi_shape_value(cv_info.as_bytes())
+                        - vowels;
+                }
+            }
+        }
+        score
+    }
+}
+
+/// Names of the built-in scorers accepted by [`scorer_by_name`]
+pub const SCORER_NAMES: [&str; 4] = ["cll", "letters", "syllables", "penalty"];
+
+/// Look up a built-in scorer by its [`LujvoScorer::name`]
+pub fn scorer_by_name(name: &str) -> Option<Box<dyn LujvoScorer>> {
+    match name {
+        "cll" => Some(Box::new(Cll1994)),
+        "letters" => Some(Box::new(ShortestLetters)),
+        "syllables" => Some(Box::new(SyllableCount)),
+        "penalty" => Some(Box::new(HyphenPenalty::default())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_strings(parts: &[&str]) -> Vec<String> {
+        parts.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_breakdown_matches_score() {
+        for parts in [
+            vec!["kla", "gau"],
+            vec!["klam", "y", "gau"],
+            vec!["ca'i", "r", "gau"],
+            vec!["bra", "mlatu"],
+            vec!["ki'o", "n", "ta'u"],
+            vec!["lojbo", "mupli"],
+            vec!["sel", "ja'e", "gau"],
+            vec!["zbasu"],
+        ] {
+            let parts = to_strings(&parts);
+            assert_eq!(get_lujvo_score_breakdown(&parts).score, get_lujvo_score(&parts));
+        }
+    }
+
+    #[test]
+    fn test_breakdown_terms() {
+        let breakdown = get_lujvo_score_breakdown(&to_strings(&["klam", "y", "gau"]));
+        assert_eq!(breakdown.letters, 8);
+        assert_eq!(breakdown.apostrophes, 0);
+        assert_eq!(breakdown.hyphens, 1);
+        assert_eq!(breakdown.rafsi_value, 12);
+        assert_eq!(breakdown.vowels, 3);
+        assert_eq!(
+            breakdown.rafsi,
+            vec![
+                RafsiContribution { rafsi: "klam".into(), shape: "CCVC".into(), value: 4 },
+                RafsiContribution { rafsi: "gau".into(), shape: "CVV".into(), value: 8 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scorers() {
+        let klagau = to_strings(&["kla", "gau"]);
+        let klamygau = to_strings(&["klam", "y", "gau"]);
+        assert_eq!(Cll1994.score(&klagau), get_lujvo_score(&klagau));
+        for name in SCORER_NAMES {
+            let scorer = scorer_by_name(name).unwrap();
+            assert_eq!(scorer.name(), name);
+            assert!(scorer.score(&klagau) < scorer.score(&klamygau), "{}", name);
+        }
+        assert!(scorer_by_name("foo").is_none());
+
+        // an apostrophe costs half a letter in CLL but a whole one by default here
+        let cahirgau = to_strings(&["ca'i", "r", "gau"]);
+        let cakcygau = to_strings(&["cak", "y", "gau"]);
+        assert_eq!(
+            HyphenPenalty::default().score(&cahirgau),
+            get_lujvo_score(&cahirgau) + 500 - 100
+        );
+        assert!(HyphenPenalty::default().score(&cahirgau) < HyphenPenalty::default().score(&cakcygau));
+    }
+}
@@ -1,11 +1,18 @@
 use std::collections::HashMap;
 
-use serde_json;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use serde::Serialize;
+use smallvec::SmallVec;
 
 use super::rafsi_list::{
     get_cmavo_rafsi_list, get_cmavo_rafsi_list_exp, get_gismu_rafsi_list, get_gismu_rafsi_list_exp,
 };
-use super::{jvokaha, narge};
+use super::dictionary::RafsiSource;
+use super::scoring::{Cll1994, LujvoScorer};
+use super::{jvokaha, narge, JvozbaLimits};
+use crate::error::{SelrafsiError, SelrafsiProblem, VlazbaError};
+use crate::valsi::{classify_valsi, ValsiClass};
 
 #[derive(Clone)]
 pub struct RafsiOptions<'a> {
@@ -16,25 +23,106 @@ pub struct RafsiOptions<'a> {
     pub custom_gismu_exp: Option<&'a HashMap<String, Vec<String>>>,
 }
 
-pub fn create_every_possibility<T: Clone + serde::Serialize + for<'de> serde::Deserialize<'de>>(
-    aa: Vec<Vec<T>>,
-) -> Vec<Vec<T>> {
-    let mut arr_arr: Vec<Vec<T>> =
-        serde_json::from_str(&serde_json::to_string(&aa).unwrap()).unwrap();
-    if arr_arr.is_empty() {
-        return vec![vec![]];
-    }
-    let arr = arr_arr.pop().unwrap();
-
-    let mut result: Vec<Vec<T>> = Vec::new();
-    for e in arr {
-        let sub_results = create_every_possibility(arr_arr.clone());
-        for mut f in sub_results {
-            f.push(e.clone());
-            result.push(f);
+/// One rafsi (or other element) picked from each input list, borrowed from the input
+pub type Possibility<'a, T> = SmallVec<[&'a T; 8]>;
+
+/// Lazy iterator over the Cartesian product of a list of lists
+///
+/// Combinations are yielded in lexicographic order of their indices, the last
+/// list varying fastest. Elements are borrowed, so nothing is cloned.
+pub struct EveryPossibility<'a, T> {
+    lists: &'a [Vec<T>],
+    indices: SmallVec<[usize; 8]>,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for EveryPossibility<'a, T> {
+    type Item = Possibility<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
         }
+        self.remaining -= 1;
+
+        let item = self
+            .lists
+            .iter()
+            .zip(&self.indices)
+            .map(|(list, &i)| &list[i])
+            .collect();
+
+        for (list, i) in self.lists.iter().zip(self.indices.iter_mut()).rev() {
+            *i += 1;
+            if *i < list.len() {
+                break;
+            }
+            *i = 0;
+        }
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
     }
-    result
+}
+
+impl<T> ExactSizeIterator for EveryPossibility<'_, T> {}
+
+/// Number of combinations in the Cartesian product of `lists`, or None on overflow
+pub fn count_possibilities<T>(lists: &[Vec<T>]) -> Option<usize> {
+    lists
+        .iter()
+        .try_fold(1usize, |acc, list| acc.checked_mul(list.len()))
+}
+
+/// Iterate lazily over every way to pick one element from each list
+///
+/// # Panics
+/// Panics if the number of combinations does not fit into `usize`
+pub fn every_possibility<T>(lists: &[Vec<T>]) -> EveryPossibility<'_, T> {
+    EveryPossibility {
+        lists,
+        indices: SmallVec::from_elem(0, lists.len()),
+        remaining: count_possibilities(lists).expect("Too many combinations to enumerate"),
+    }
+}
+
+/// Get the combination at position `index` of [`every_possibility`]
+#[cfg(feature = "parallel")]
+fn possibility_at<T>(lists: &[Vec<T>], mut index: usize) -> Possibility<'_, T> {
+    let mut item: Possibility<'_, T> = lists
+        .iter()
+        .rev()
+        .map(|list| {
+            let choice = &list[index % list.len()];
+            index /= list.len();
+            choice
+        })
+        .collect();
+    item.reverse();
+    item
+}
+
+/// Parallel counterpart of [`every_possibility`], yielding combinations in the same order
+///
+/// # Panics
+/// Panics if the number of combinations does not fit into `usize`
+#[cfg(feature = "parallel")]
+pub fn par_every_possibility<T: Sync>(
+    lists: &[Vec<T>],
+) -> impl IndexedParallelIterator<Item = Possibility<'_, T>> {
+    let total = count_possibilities(lists).expect("Too many combinations to enumerate");
+    (0..total)
+        .into_par_iter()
+        .map(move |index| possibility_at(lists, index))
+}
+
+#[deprecated(note = "use `every_possibility`, which does not materialize every combination")]
+pub fn create_every_possibility<T: Clone>(aa: Vec<Vec<T>>) -> Vec<Vec<T>> {
+    every_possibility(&aa)
+        .map(|p| p.into_iter().cloned().collect())
+        .collect()
 }
 
 pub fn gismu_rafsi_list(
@@ -105,91 +193,169 @@ pub fn cmavo_rafsi_list(
     None
 }
 
-pub fn get_candid(selrafsi: &str, is_last: bool, options: &RafsiOptions) -> Vec<String> {
-    if let Some(a) = cmavo_rafsi_list(
-        selrafsi,
-        options.exp_rafsi,
-        options.custom_cmavo,
-        options.custom_cmavo_exp,
-    ) {
+pub fn get_candid(
+    selrafsi: &str,
+    is_last: bool,
+    options: &(impl RafsiSource + ?Sized),
+) -> Vec<String> {
+    if let Some(a) = options.cmavo_rafsi(selrafsi) {
         return a;
     }
-    if let Some(b) = gismu_rafsi_list(
-        selrafsi,
-        options.exp_rafsi,
-        options.custom_gismu,
-        options.custom_gismu_exp,
-    ) {
-        let gismu = selrafsi;
-        let mut candid = b;
-
-        if is_last {
-            candid.push(gismu.to_string());
-        }
+    let gismu = selrafsi;
+    let mut candid = options.gismu_rafsi(gismu);
 
-        let chopped = gismu
-            .chars()
-            .take(gismu.chars().count() - 1)
-            .collect::<String>();
-        if chopped != "brod" {
-            candid.push(chopped);
-        }
-        candid
+    if is_last {
+        candid.push(gismu.to_string());
+    }
+
+    let chopped = gismu
+        .chars()
+        .take(gismu.chars().count() - 1)
+        .collect::<String>();
+    if chopped != "brod" {
+        candid.push(chopped);
+    }
+    candid
+}
+
+/// Check that every word of a tanru has rafsi or is a gismu whose letters can serve as rafsi
+///
+/// # Returns
+/// `VlazbaError::InvalidSelrafsi` listing every unusable word
+pub fn check_selrafsi(
+    arr: &[String],
+    options: &(impl RafsiSource + ?Sized),
+) -> Result<(), VlazbaError> {
+    let errors: Vec<SelrafsiError> = arr
+        .iter()
+        .enumerate()
+        .filter_map(|(position, word)| {
+            let problem = selrafsi_problem(word, options)?;
+            Some(SelrafsiError {
+                word: word.clone(),
+                position,
+                problem,
+            })
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
     } else {
-        Vec::new()
+        Err(VlazbaError::InvalidSelrafsi(errors))
     }
 }
 
+fn selrafsi_problem(word: &str, options: &(impl RafsiSource + ?Sized)) -> Option<SelrafsiProblem> {
+    let has_rafsi = options.cmavo_rafsi(word).is_some() || !options.gismu_rafsi(word).is_empty();
+    if has_rafsi {
+        return None;
+    }
+    match classify_valsi(word) {
+        ValsiClass::Gismu { .. } => None,
+        ValsiClass::Cmavo => Some(SelrafsiProblem::CmavoWithoutRafsi),
+        class => Some(SelrafsiProblem::NotGismu(class)),
+    }
+}
+
+/// A lujvo and the canonical lujvo its components give
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReconstructedLujvo {
+    pub lujvo: String,
+    pub reconstructed: String,
+}
+
 /// Reconstruct a lujvo from its components
 ///
+/// This searches without limits; use [`try_reconstruct_lujvo`] for untrusted input.
+///
 /// # Arguments
 /// * `lujvo` - The lujvo to reconstruct
-/// * `exp_rafsi` - Whether to use experimental rafsi
+/// * `forbid_cmevla` - Whether to forbid cmevla (name words)
+/// * `options` - Rafsi lists to use
 ///
 /// # Returns
-/// Result with reconstructed lujvo or error message
+/// Result with reconstructed lujvo, or why it could not be reconstructed
 pub fn reconstruct_lujvo(
     lujvo: &str,
     forbid_cmevla: bool,
-    options: &RafsiOptions,
-) -> Result<String, Box<dyn std::error::Error>> {
-    // Split into rafsi
-    let rafsi_list = jvokaha::jvokaha(lujvo)?;
+    options: &(impl RafsiSource + ?Sized),
+) -> Result<String, VlazbaError> {
+    try_reconstruct_lujvo(lujvo, forbid_cmevla, options, &Cll1994, &JvozbaLimits::default())
+}
 
-    // Get selrafsi for each rafsi
-    let selrafsi_list: Vec<String> = rafsi_list
-        .iter()
-        .filter_map(|rafsi| {
-            if rafsi == "y" || rafsi == "r" || rafsi == "n" {
-                None
-            } else {
-                search_selrafsi_from_rafsi2(
-                    rafsi,
-                    options,
-                )
-            }
-        })
-        .collect();
+/// Reconstruct a lujvo from its components, ranking with `scorer` and giving up once
+/// the jvozba search exceeds `limits`
+pub fn try_reconstruct_lujvo(
+    lujvo: &str,
+    forbid_cmevla: bool,
+    options: &(impl RafsiSource + ?Sized),
+    scorer: &dyn LujvoScorer,
+    limits: &JvozbaLimits,
+) -> Result<String, VlazbaError> {
+    let selrafsi_list = jvokaha::jvokaha_selrafsi(lujvo, options)?;
 
     // Rebuild using jvozba
-    let rebuilt = narge::jvozba(
-        &selrafsi_list,
-        false,
-        forbid_cmevla,
-        options,
-    )
-    .first()
-    .ok_or("Failed to rebuild lujvo")?
-    .lujvo
-    .clone();
-
-    Ok(rebuilt)
+    let rebuilt = narge::try_jvozba(&selrafsi_list, false, forbid_cmevla, options, scorer, limits)?;
+    rebuilt
+        .into_iter()
+        .next()
+        .map(|best| best.lujvo)
+        .ok_or(VlazbaError::NoLujvo {
+            selrafsi: selrafsi_list,
+        })
+}
+
+/// The best selrafsi for a rafsi; see [`RafsiSource::selrafsi_candidates`] for every candidate
+pub fn search_selrafsi_from_rafsi2(
+    rafsi: &str,
+    options: &(impl RafsiSource + ?Sized),
+) -> Option<String> {
+    options
+        .selrafsi_candidates(rafsi)
+        .into_iter()
+        .next()
+        .map(|candidate| candidate.selrafsi)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_check_selrafsi() {
+        let options = RafsiOptions {
+            exp_rafsi: false,
+            custom_cmavo: None,
+            custom_cmavo_exp: None,
+            custom_gismu: None,
+            custom_gismu_exp: None,
+        };
+        let words = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        assert!(check_selrafsi(&words("klama gasnu na brodu"), &options).is_ok());
+        // gismu without rafsi of their own are still usable
+        assert!(check_selrafsi(&words("klama tsaga"), &options).is_ok());
+
+        let Err(VlazbaError::InvalidSelrafsi(errors)) = check_selrafsi(&words("foobar ku gasnu"), &options) else {
+            panic!("Expected invalid selrafsi");
+        };
+        assert_eq!(
+            errors,
+            vec![
+                SelrafsiError {
+                    word: "foobar".into(),
+                    position: 0,
+                    problem: SelrafsiProblem::NotGismu(ValsiClass::Cmevla),
+                },
+                SelrafsiError {
+                    word: "ku".into(),
+                    position: 1,
+                    problem: SelrafsiProblem::CmavoWithoutRafsi,
+                },
+            ]
+        );
+    }
+
     #[test]
     fn test_reconstruct_lujvo_basic() {
         let options = RafsiOptions {
@@ -212,6 +378,20 @@ mod tests {
         assert_eq!(result, "bramlatu");
     }
 
+    #[test]
+    fn test_try_reconstruct_lujvo_limits() {
+        let dictionary = crate::jvozba::dictionary::RafsiDictionary::official();
+        let limits = JvozbaLimits {
+            max_components: Some(2),
+            ..JvozbaLimits::default()
+        };
+        assert_eq!(try_reconstruct_lujvo("klagasnu", false, &dictionary, &Cll1994, &limits).unwrap(), "klagau");
+        assert!(matches!(
+            try_reconstruct_lujvo("klaklagau", false, &dictionary, &Cll1994, &limits),
+            Err(VlazbaError::SearchLimit(_))
+        ));
+    }
+
     #[test]
     fn test_reconstruct_lujvo_with_y_hyphen() {
         let options = RafsiOptions {
@@ -264,7 +444,45 @@ mod tests {
     }
 
     #[test]
-    fn test_reconstruct_empty_string() {
+    fn test_every_possibility_order() {
+        let lists = vec![vec!["a", "b"], vec!["x"], vec!["1", "2"]];
+        let result: Vec<Vec<&str>> = every_possibility(&lists)
+            .map(|p| p.into_iter().copied().collect())
+            .collect();
+        assert_eq!(
+            result,
+            vec![
+                vec!["a", "x", "1"],
+                vec!["a", "x", "2"],
+                vec!["b", "x", "1"],
+                vec!["b", "x", "2"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_every_possibility_edge_cases() {
+        let no_lists: Vec<Vec<u8>> = vec![];
+        assert_eq!(every_possibility(&no_lists).count(), 1);
+
+        let with_empty = vec![vec![1, 2], vec![]];
+        assert_eq!(every_possibility(&with_empty).count(), 0);
+        #[cfg(feature = "parallel")]
+        assert_eq!(par_every_possibility(&with_empty).count(), 0);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_par_every_possibility_matches_sequential() {
+        let lists = vec![vec![1, 2, 3], vec![4, 5], vec![6, 7, 8, 9]];
+        let sequential: Vec<_> = every_possibility(&lists).collect();
+        let parallel: Vec<_> = par_every_possibility(&lists).collect();
+        assert_eq!(sequential, parallel);
+        assert_eq!(count_possibilities(&lists), Some(24));
+    }
+
+    #[test]
+    fn test_reconstruct_unknown_rafsi() {
         let options = RafsiOptions {
             exp_rafsi: false,
             custom_cmavo: None,
@@ -272,54 +490,34 @@ mod tests {
             custom_gismu: None,
             custom_gismu_exp: None,
         };
-        assert!(reconstruct_lujvo("", false, &options).is_err());
-    }
-}
-
-pub fn search_selrafsi_from_rafsi2(
-    rafsi: &str,
-    options: &RafsiOptions,
-) -> Option<String> {
-    if let Some(rafsis) = gismu_rafsi_list(rafsi, options.exp_rafsi, options.custom_gismu, options.custom_gismu_exp) {
-        if !rafsis.is_empty() {
-            return Some(rafsi.to_owned());
-        }
+        assert_eq!(
+            reconstruct_lujvo("klazuvgau", true, &options),
+            Err(VlazbaError::UnknownRafsi { rafsi: "zuv".to_string(), offset: 3 })
+        );
     }
 
-    if rafsi != "brod" && rafsi.len() == 4 && !rafsi.contains('\'') {
-        for vowel in "aeiou".chars() {
-            let gismu_candid = format!("{}{}", rafsi, vowel);
-            if gismu_rafsi_list(&gismu_candid, options.exp_rafsi, None, None).is_some() {
-                return Some(gismu_candid);
-            }
-        }
-    }
-
-    for (i, rafsi_list) in get_gismu_rafsi_list().iter() {
-        if rafsi_list.contains(&rafsi.to_string()) {
-            return Some(i.clone());
-        }
-    }
-
-    for (j, rafsi_list) in get_cmavo_rafsi_list().iter() {
-        if rafsi_list.contains(&rafsi.to_string()) {
-            return Some(j.clone());
-        }
+    #[test]
+    fn test_reconstruct_four_letter_rafsi() {
+        let options = RafsiOptions {
+            exp_rafsi: false,
+            custom_cmavo: None,
+            custom_cmavo_exp: None,
+            custom_gismu: None,
+            custom_gismu_exp: None,
+        };
+        assert_eq!(search_selrafsi_from_rafsi2("gasn", &options), Some("gasnu".to_string()));
+        assert_eq!(reconstruct_lujvo("gasnyklama", true, &options), Ok("gaukla".to_string()));
     }
 
-    if options.exp_rafsi {
-        for (i, rafsi_list) in get_gismu_rafsi_list_exp().iter() {
-            if rafsi_list.contains(&rafsi.to_string()) {
-                return Some(i.clone());
-            }
-        }
-
-        for (j, rafsi_list) in get_cmavo_rafsi_list_exp().iter() {
-            if rafsi_list.contains(&rafsi.to_string()) {
-                return Some(j.clone());
-            }
-        }
+    #[test]
+    fn test_reconstruct_empty_string() {
+        let options = RafsiOptions {
+            exp_rafsi: false,
+            custom_cmavo: None,
+            custom_cmavo_exp: None,
+            custom_gismu: None,
+            custom_gismu_exp: None,
+        };
+        assert!(reconstruct_lujvo("", false, &options).is_err());
     }
-
-    None
 }
@@ -0,0 +1,426 @@
+use std::ops::Range;
+
+use crate::libs::config::VALID_CC_INITIALS;
+
+use super::jvokaha::{LujvoPart, LujvoPartKind};
+use super::narge::{is_c, is_permissible, normalize};
+
+/// Kind of a morphological problem found by [`validate_lujvo`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ViolationKind {
+    /// A character that is not a Lojban letter
+    UnknownLetter,
+    /// The rest of the word cannot be split into rafsi
+    Undecomposable,
+    /// The word contains fewer than two rafsi
+    TooFewRafsi,
+    /// Two adjacent consonants that may not stand next to each other
+    ImpermissibleConsonants,
+    /// A rafsi starts with a consonant pair that is not a permissible initial
+    IllegalInitialCluster,
+    /// A required y, r or n hyphen is missing
+    MissingHyphen,
+    /// A hyphen where none is allowed
+    ExtraHyphen,
+    /// A y, r or n hyphen where a different hyphen is required
+    WrongHyphen,
+    /// The first rafsi would fall off as a cmavo without a y hyphen
+    Tosmabru,
+    /// The word ends in a rafsi that cannot end a lujvo
+    BadFinalRafsi,
+}
+
+/// A single problem found by [`validate_lujvo`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub kind: ViolationKind,
+    /// Character range of the word the problem applies to; empty where a letter is missing
+    pub span: Range<usize>,
+    pub message: String,
+    /// Text to replace `span` with to fix the problem, where there is a mechanical fix
+    pub suggestion: Option<String>,
+}
+
+/// Check a lujvo against the morphology rules of CLL chapter 4
+///
+/// Unlike [`jvokaha`](super::jvokaha::jvokaha), which stops at the first problem,
+/// this reports every violation it can find.
+///
+/// # Arguments
+/// * `lujvo` - The compound word to check
+///
+/// # Returns
+/// Diagnostics ordered by position; empty if the word is a valid lujvo
+pub fn validate_lujvo(lujvo: &str) -> Vec<Diagnostic> {
+    let unknown: Vec<Diagnostic> = lujvo
+        .chars()
+        .enumerate()
+        .filter(|&(_, c)| !is_lojban_letter(c))
+        .map(|(i, c)| Diagnostic {
+            kind: ViolationKind::UnknownLetter,
+            span: i..i + 1,
+            message: format!("{{{}}} is not a Lojban letter", c),
+            suggestion: None,
+        })
+        .collect();
+    if !unknown.is_empty() {
+        return unknown;
+    }
+
+    // Only ASCII letters are left, so byte offsets are character offsets
+    let splits = match split_leniently(lujvo) {
+        Ok(splits) => splits,
+        Err(offset) => {
+            return vec![Diagnostic {
+                kind: ViolationKind::Undecomposable,
+                span: offset..lujvo.len(),
+                message: format!("{{{}}} cannot be split into rafsi", &lujvo[offset..]),
+                suggestion: None,
+            }]
+        }
+    };
+
+    // A word can often be split in more than one way; report the most charitable reading
+    splits
+        .iter()
+        .map(|parts| {
+            let mut diagnostics = Vec::new();
+            check_clusters(parts, &mut diagnostics);
+            check_hyphens(lujvo, parts, &mut diagnostics);
+            check_final(parts, &mut diagnostics);
+            diagnostics.sort_by_key(|d| (d.span.start, d.span.end));
+            diagnostics
+        })
+        .min_by_key(Vec::len)
+        .unwrap_or_default()
+}
+
+#[inline]
+fn is_lojban_letter(c: char) -> bool {
+    is_c(c) || "aeiouy'".contains(c)
+}
+
+/// Every way to split a word into rafsi and hyphens, accepting hyphens where they are not
+/// needed and leaving them out where they are, so that each split can be checked afterwards
+///
+/// On failure, returns the furthest position any split reached.
+fn split_leniently(lujvo: &str) -> Result<Vec<Vec<LujvoPart>>, usize> {
+    let mut splits = Vec::new();
+    let mut furthest = 0;
+    split_from(lujvo, 0, &mut Vec::new(), &mut splits, &mut furthest);
+    if splits.is_empty() {
+        Err(furthest)
+    } else {
+        Ok(splits)
+    }
+}
+
+fn split_from(
+    lujvo: &str,
+    pos: usize,
+    parts: &mut Vec<LujvoPart>,
+    splits: &mut Vec<Vec<LujvoPart>>,
+    furthest: &mut usize,
+) {
+    *furthest = (*furthest).max(pos);
+    if pos == lujvo.len() {
+        if !parts.is_empty() {
+            splits.push(parts.clone());
+        }
+        return;
+    }
+
+    let rest = &lujvo[pos..];
+    for len in 1..=5.min(rest.len()) {
+        let text = &rest[..len];
+        let Some(kind) = LujvoPartKind::from_rafsi(text) else {
+            continue;
+        };
+        let allowed = match kind {
+            LujvoPartKind::YHyphen => parts.last().is_some_and(|p| !p.kind.is_hyphen()),
+            LujvoPartKind::RHyphen | LujvoPartKind::NHyphen => {
+                parts.last().is_some_and(|p| !p.kind.is_hyphen())
+                    && rest[1..].starts_with(|c: char| is_c(c))
+            }
+            LujvoPartKind::Cvv => ["ai", "ei", "oi", "au"].contains(&&text[1..]),
+            LujvoPartKind::Cvccv | LujvoPartKind::Ccvcv => len == rest.len(),
+            _ => true,
+        };
+        if !allowed {
+            continue;
+        }
+
+        parts.push(LujvoPart {
+            text: text.to_string(),
+            kind,
+            span: pos..pos + len,
+        });
+        split_from(lujvo, pos + len, parts, splits, furthest);
+        parts.pop();
+    }
+}
+
+fn check_clusters(parts: &[LujvoPart], diagnostics: &mut Vec<Diagnostic>) {
+    for part in parts {
+        let start = part.span.start;
+        match part.kind {
+            LujvoPartKind::Ccv | LujvoPartKind::Ccvc | LujvoPartKind::Ccvcv => {
+                let initial = &part.text[..2];
+                if !VALID_CC_INITIALS.contains(&initial) {
+                    diagnostics.push(Diagnostic {
+                        kind: ViolationKind::IllegalInitialCluster,
+                        span: start..start + 2,
+                        message: format!(
+                            "{{{}}} cannot start the rafsi {{{}}}",
+                            initial, part.text
+                        ),
+                        suggestion: None,
+                    });
+                }
+            }
+            LujvoPartKind::Cvcc | LujvoPartKind::Cvccv => {
+                let mut medial = part.text[2..4].chars();
+                let (c1, c2) = (medial.next().unwrap(), medial.next().unwrap());
+                if is_permissible(c1, c2) == 0 {
+                    diagnostics.push(Diagnostic {
+                        kind: ViolationKind::ImpermissibleConsonants,
+                        span: start + 2..start + 4,
+                        message: format!(
+                            "{{{}{}}} is not a permissible consonant pair in {{{}}}",
+                            c1, c2, part.text
+                        ),
+                        suggestion: None,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn check_hyphens(lujvo: &str, parts: &[LujvoPart], diagnostics: &mut Vec<Diagnostic>) {
+    let rafsi: Vec<&LujvoPart> = parts.iter().filter(|p| !p.kind.is_hyphen()).collect();
+    if rafsi.len() < 2 {
+        diagnostics.push(Diagnostic {
+            kind: ViolationKind::TooFewRafsi,
+            span: 0..lujvo.len(),
+            message: "You need at least two rafsi to make a lujvo".to_string(),
+            suggestion: None,
+        });
+        return;
+    }
+    let Ok(expected) = normalize(&rafsi.iter().map(|p| p.text.as_str()).collect::<Vec<_>>())
+    else {
+        return;
+    };
+
+    let actual_hyphens = hyphens_after_rafsi(parts.iter().map(|p| (p.kind.is_hyphen(), p)));
+    let expected_hyphens = hyphens_after_rafsi(
+        expected
+            .iter()
+            .map(|s| (LujvoPartKind::from_rafsi(s).is_some_and(|k| k.is_hyphen()), s)),
+    );
+
+    for (i, (actual, expected)) in actual_hyphens.iter().zip(&expected_hyphens).enumerate() {
+        let left = rafsi[i];
+        let pos = left.span.end;
+        match (actual, expected) {
+            (None, None) => {}
+            (Some(hyphen), None) => diagnostics.push(Diagnostic {
+                kind: ViolationKind::ExtraHyphen,
+                span: hyphen.span.clone(),
+                message: format!("No hyphen is needed after {{{}}}", left.text),
+                suggestion: Some(String::new()),
+            }),
+            (Some(hyphen), Some(wanted)) => {
+                if hyphen.text != **wanted {
+                    diagnostics.push(Diagnostic {
+                        kind: ViolationKind::WrongHyphen,
+                        span: hyphen.span.clone(),
+                        message: format!(
+                            "{{{}}} must be followed by the {} hyphen, not {}",
+                            left.text, wanted, hyphen.text
+                        ),
+                        suggestion: Some(wanted.to_string()),
+                    });
+                }
+            }
+            (None, Some(wanted)) => {
+                diagnostics.push(missing_hyphen(lujvo, left, rafsi[i + 1], pos, wanted))
+            }
+        }
+    }
+
+    if let Some(hyphen) = parts.last().filter(|p| p.kind.is_hyphen()) {
+        diagnostics.push(Diagnostic {
+            kind: ViolationKind::ExtraHyphen,
+            span: hyphen.span.clone(),
+            message: "A lujvo cannot end in a hyphen".to_string(),
+            suggestion: Some(String::new()),
+        });
+    }
+}
+
+/// For each rafsi but the last, the hyphen that follows it, if any
+fn hyphens_after_rafsi<'a, T: 'a>(
+    parts: impl Iterator<Item = (bool, &'a T)>,
+) -> Vec<Option<&'a T>> {
+    let mut hyphens: Vec<Option<&T>> = Vec::new();
+    for (is_hyphen, part) in parts {
+        if !is_hyphen {
+            hyphens.push(None);
+        } else if let Some(last) = hyphens.last_mut() {
+            *last = Some(part);
+        }
+    }
+    // there is no boundary after the final rafsi
+    hyphens.pop();
+    hyphens
+}
+
+fn missing_hyphen(
+    lujvo: &str,
+    left: &LujvoPart,
+    right: &LujvoPart,
+    pos: usize,
+    wanted: &str,
+) -> Diagnostic {
+    let end = left.text.chars().last().unwrap();
+    let init = right.text.chars().next().unwrap();
+
+    if left.kind.is_4letter_rafsi() {
+        Diagnostic {
+            kind: ViolationKind::MissingHyphen,
+            span: pos..pos,
+            message: format!("The 4-letter rafsi {{{}}} must be followed by y", left.text),
+            suggestion: Some(wanted.to_string()),
+        }
+    } else if wanted != "y" {
+        Diagnostic {
+            kind: ViolationKind::MissingHyphen,
+            span: pos..pos,
+            message: format!(
+                "The initial rafsi {{{}}} must be followed by the {} hyphen",
+                left.text, wanted
+            ),
+            suggestion: Some(wanted.to_string()),
+        }
+    } else if is_c(end) && is_c(init) && is_permissible(end, init) == 0 {
+        Diagnostic {
+            kind: ViolationKind::ImpermissibleConsonants,
+            span: pos - 1..pos + 1,
+            message: format!("{{{}{}}} is not a permissible consonant pair", end, init),
+            suggestion: Some(format!("{}y{}", end, init)),
+        }
+    } else if end == 'n' && ["ts", "tc", "dz", "dj"].iter().any(|s| right.text.starts_with(s)) {
+        Diagnostic {
+            kind: ViolationKind::ImpermissibleConsonants,
+            span: pos - 1..pos + 2,
+            message: format!("{{{}}} is not a permissible consonant triple", &lujvo[pos - 1..pos + 2]),
+            suggestion: Some(format!("ny{}", &lujvo[pos..pos + 2])),
+        }
+    } else {
+        Diagnostic {
+            kind: ViolationKind::Tosmabru,
+            span: pos..pos,
+            message: format!(
+                "{{{}}} would fall off the front of the word without a y hyphen",
+                left.text
+            ),
+            suggestion: Some(wanted.to_string()),
+        }
+    }
+}
+
+fn check_final(parts: &[LujvoPart], diagnostics: &mut Vec<Diagnostic>) {
+    let Some(last) = parts.iter().rev().find(|p| !p.kind.is_hyphen()) else {
+        return;
+    };
+    if matches!(
+        last.kind,
+        LujvoPartKind::Cvc | LujvoPartKind::Cvcc | LujvoPartKind::Ccvc
+    ) {
+        diagnostics.push(Diagnostic {
+            kind: ViolationKind::BadFinalRafsi,
+            span: last.span.clone(),
+            message: format!(
+                "A lujvo cannot end in the {} rafsi {{{}}}; use a rafsi ending in a vowel or the full gismu",
+                last.kind.shape(),
+                last.text
+            ),
+            suggestion: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(lujvo: &str) -> Vec<ViolationKind> {
+        validate_lujvo(lujvo).into_iter().map(|d| d.kind).collect()
+    }
+
+    #[test]
+    fn test_valid_lujvo() {
+        for lujvo in ["bramlatu", "klamyseltru", "toirbroda", "ca'irgau", "klagau"] {
+            assert!(validate_lujvo(lujvo).is_empty(), "{} should be valid", lujvo);
+        }
+    }
+
+    #[test]
+    fn test_missing_hyphens() {
+        let diagnostics = validate_lujvo("klamseltru");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, ViolationKind::MissingHyphen);
+        assert_eq!(diagnostics[0].span, 4..4);
+        assert_eq!(diagnostics[0].suggestion.as_deref(), Some("y"));
+
+        let diagnostics = validate_lujvo("toibroda");
+        assert_eq!(diagnostics[0].kind, ViolationKind::MissingHyphen);
+        assert_eq!(diagnostics[0].suggestion.as_deref(), Some("r"));
+
+        assert_eq!(kinds("tosmabru"), vec![ViolationKind::Tosmabru]);
+    }
+
+    #[test]
+    fn test_extra_hyphen() {
+        let diagnostics = validate_lujvo("braymlatu");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, ViolationKind::ExtraHyphen);
+        assert_eq!(diagnostics[0].span, 3..4);
+        assert_eq!(diagnostics[0].suggestion.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_consonant_rules() {
+        let diagnostics = validate_lujvo("sebpre");
+        assert_eq!(diagnostics[0].kind, ViolationKind::ImpermissibleConsonants);
+        assert_eq!(diagnostics[0].span, 2..4);
+        assert_eq!(diagnostics[0].suggestion.as_deref(), Some("byp"));
+
+        assert_eq!(kinds("mseklama"), vec![ViolationKind::IllegalInitialCluster]);
+    }
+
+    #[test]
+    fn test_reports_every_problem() {
+        assert_eq!(
+            kinds("mseklamgasn"),
+            vec![
+                ViolationKind::IllegalInitialCluster,
+                ViolationKind::MissingHyphen,
+                ViolationKind::BadFinalRafsi,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unsplittable_words() {
+        assert_eq!(kinds("klaqgau"), vec![ViolationKind::UnknownLetter]);
+        assert_eq!(kinds("klama"), vec![ViolationKind::TooFewRafsi]);
+
+        let diagnostics = validate_lujvo("klasr");
+        assert_eq!(diagnostics[0].kind, ViolationKind::Undecomposable);
+        assert_eq!(diagnostics[0].span, 4..5);
+    }
+}
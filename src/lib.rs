@@ -24,14 +24,24 @@ assert_eq!(decomposition, vec!["kal", "ga'u"]);
 ```
 */
 
+pub mod error;
 pub mod gismu_utils;
 pub mod jvozba;
 pub mod libs;
+pub mod syllables;
+pub mod valsi;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 pub use jvozba::{
+    dictionary::{RafsiDictionary, RafsiSource},
     jvokaha,
     jvozba,
     scoring::get_lujvo_score,
-    tools::{get_candid, search_selrafsi_from_rafsi2, reconstruct_lujvo},
+    tools::{every_possibility, get_candid, search_selrafsi_from_rafsi2, reconstruct_lujvo},
 };
+#[cfg(feature = "parallel")]
+pub use jvozba::tools::par_every_possibility;
+pub use error::VlazbaError;
 pub use gismu_utils::{GismuGenerator, GismuMatcher, GismuScorer};
+pub use valsi::{classify_valsi, ValsiClass};
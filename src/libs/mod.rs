@@ -1,2 +1,6 @@
 pub mod cli;
 pub mod config;
+pub mod output;
+pub mod settings;
+#[cfg(feature = "server")]
+pub mod server;
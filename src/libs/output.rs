@@ -0,0 +1,306 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::gismu_utils::{GismuCandidate, SimilarGismu};
+use crate::jvozba::{jvokaha::LujvoDecomposition, tools::ReconstructedLujvo, LujvoAndScore};
+
+/// Names accepted by [`OutputFormat::from_name`]
+pub const FORMAT_NAMES: [&str; 4] = ["text", "json", "jsonl", "csv"];
+
+/// How the CLI writes its results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON array of all records
+    Json,
+    /// One JSON object per line
+    Jsonl,
+    /// A header line and one row per record
+    Csv,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            "jsonl" => Some(OutputFormat::Jsonl),
+            "csv" => Some(OutputFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// A result the CLI can write in every [`OutputFormat`]
+pub trait Record: Serialize {
+    /// Column names of [`Record::csv_row`]
+    fn csv_header() -> Vec<&'static str>;
+
+    /// Fields in the order of [`Record::csv_header`]; lists are joined with spaces
+    fn csv_row(&self) -> Vec<String>;
+
+    /// Human-readable form, possibly several lines
+    fn text(&self) -> String;
+}
+
+/// Write records in the given format
+pub fn write_records<R: Record>(
+    out: &mut impl Write,
+    format: OutputFormat,
+    records: &[R],
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Text => {
+            for record in records {
+                writeln!(out, "{}", record.text())?;
+            }
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, records)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Jsonl => {
+            for record in records {
+                serde_json::to_writer(&mut *out, record)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Csv => {
+            writeln!(out, "{}", R::csv_header().join(","))?;
+            for record in records {
+                let row: Vec<String> = record.csv_row().iter().map(|f| csv_field(f)).collect();
+                writeln!(out, "{}", row.join(","))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// The result for one line of a batch, or why the line failed
+#[derive(Debug, Clone, Serialize)]
+pub struct LineRecord<R> {
+    /// 1-based line number in the input
+    pub line: usize,
+    pub input: String,
+    #[serde(flatten)]
+    pub result: Option<R>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl<R> LineRecord<R> {
+    pub fn new(line: usize, input: &str, result: Result<R, String>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        LineRecord {
+            line,
+            input: input.to_string(),
+            result,
+            error,
+        }
+    }
+}
+
+impl<R: Record> Record for LineRecord<R> {
+    fn csv_header() -> Vec<&'static str> {
+        let mut header = vec!["line", "input"];
+        header.extend(R::csv_header());
+        header.push("error");
+        header
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        let mut row = vec![self.line.to_string(), self.input.clone()];
+        match &self.result {
+            Some(result) => row.extend(result.csv_row()),
+            None => row.extend(R::csv_header().iter().map(|_| String::new())),
+        }
+        row.push(self.error.clone().unwrap_or_default());
+        row
+    }
+
+    /// The line number, the input and the result on one tab-separated line
+    fn text(&self) -> String {
+        let outcome = match (&self.result, &self.error) {
+            (Some(result), _) => result.text().lines().collect::<Vec<_>>().join(" "),
+            (None, error) => format!("error: {}", error.as_deref().unwrap_or_default()),
+        };
+        format!("{}\t{}\t{}", self.line, self.input, outcome)
+    }
+}
+
+impl Record for LujvoAndScore {
+    fn csv_header() -> Vec<&'static str> {
+        vec!["lujvo", "score", "scorer", "rafsi"]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.lujvo.clone(),
+            self.score.to_string(),
+            self.scorer.to_string(),
+            self.rafsi.join(" "),
+        ]
+    }
+
+    fn text(&self) -> String {
+        let mut text = format!("{}: {}", self.lujvo, self.score);
+        if let Some(breakdown) = &self.breakdown {
+            for line in breakdown.to_string().lines() {
+                text.push_str(&format!("\n    {}", line));
+            }
+        }
+        text
+    }
+}
+
+impl Record for LujvoDecomposition {
+    fn csv_header() -> Vec<&'static str> {
+        vec!["lujvo", "rafsi", "selrafsi"]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.lujvo.clone(),
+            self.rafsi.join(" "),
+            self.selrafsi_or_rafsi().join(" "),
+        ]
+    }
+
+    fn text(&self) -> String {
+        self.selrafsi_or_rafsi().join("\n")
+    }
+}
+
+impl LujvoDecomposition {
+    /// Selrafsi, with unknown rafsi written as `-rafsi-`
+    fn selrafsi_or_rafsi(&self) -> Vec<String> {
+        self.rafsi
+            .iter()
+            .zip(&self.selrafsi)
+            .map(|(rafsi, selrafsi)| selrafsi.clone().unwrap_or_else(|| format!("-{}-", rafsi)))
+            .collect()
+    }
+}
+
+impl Record for ReconstructedLujvo {
+    fn csv_header() -> Vec<&'static str> {
+        vec!["lujvo", "reconstructed"]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![self.lujvo.clone(), self.reconstructed.clone()]
+    }
+
+    fn text(&self) -> String {
+        self.reconstructed.clone()
+    }
+}
+
+impl Record for SimilarGismu {
+    fn csv_header() -> Vec<&'static str> {
+        vec!["candidate", "similar"]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![self.candidate.clone(), self.similar.join(" ")]
+    }
+
+    fn text(&self) -> String {
+        self.similar.join("\n")
+    }
+}
+
+impl Record for GismuCandidate {
+    fn csv_header() -> Vec<&'static str> {
+        vec!["candidate", "score", "language_scores"]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        let language_scores: Vec<String> =
+            self.language_scores.iter().map(|s| s.to_string()).collect();
+        vec![self.candidate.clone(), self.score.to_string(), language_scores.join(" ")]
+    }
+
+    fn text(&self) -> String {
+        format!("({:?}, {:?}, {:?})", self.score, self.candidate, self.language_scores)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jvozba::dictionary::RafsiDictionary;
+    use crate::jvozba::jvokaha::jvokaha_decomposition;
+
+    fn written<R: Record>(format: OutputFormat, records: &[R]) -> String {
+        let mut out = Vec::new();
+        write_records(&mut out, format, records).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_formats() {
+        let dictionary = RafsiDictionary::with_experimental();
+        let records = vec![
+            jvokaha_decomposition("klagau", &dictionary).unwrap(),
+            jvokaha_decomposition("klacle", &dictionary).unwrap(),
+        ];
+        assert_eq!(written(OutputFormat::Text, &records), "klama\ngasnu\nklama\n-cle-\n");
+        assert_eq!(
+            written(OutputFormat::Csv, &records),
+            "lujvo,rafsi,selrafsi\nklagau,kla gau,klama gasnu\nklacle,kla cle,klama -cle-\n"
+        );
+        assert_eq!(
+            written(OutputFormat::Jsonl, &records[..1]),
+            "{\"lujvo\":\"klagau\",\"rafsi\":[\"kla\",\"gau\"],\"selrafsi\":[\"klama\",\"gasnu\"]}\n"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&written(OutputFormat::Json, &records)).unwrap();
+        assert_eq!(json[1]["selrafsi"][1], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_line_records() {
+        let dictionary = RafsiDictionary::with_experimental();
+        let records: Vec<LineRecord<LujvoDecomposition>> = ["klagau", "klagaux"]
+            .iter()
+            .enumerate()
+            .map(|(i, lujvo)| {
+                let result = jvokaha_decomposition(lujvo, &dictionary).map_err(|e| e.to_string());
+                LineRecord::new(i + 1, lujvo, result)
+            })
+            .collect();
+        let text = written(OutputFormat::Text, &records);
+        assert!(text.starts_with("1\tklagau\tklama gasnu\n2\tklagaux\terror: "));
+        let csv = written(OutputFormat::Csv, &records);
+        assert!(csv.starts_with("line,input,lujvo,rafsi,selrafsi,error\n1,klagau,klagau,kla gau,klama gasnu,\n2,klagaux,,,,"));
+        let jsonl = written(OutputFormat::Jsonl, &records);
+        assert!(jsonl.starts_with("{\"line\":1,\"input\":\"klagau\",\"lujvo\":\"klagau\","));
+        assert!(jsonl.contains("{\"line\":2,\"input\":\"klagaux\",\"error\":"));
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"coi\""), "\"say \"\"coi\"\"\"");
+        for name in FORMAT_NAMES {
+            assert!(OutputFormat::from_name(name).is_some());
+        }
+    }
+}
@@ -0,0 +1,418 @@
+use std::borrow::Cow;
+use std::io::{self, Read};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::cli::{generate_weights, validate_words};
+use super::config::DEFAULT_WEIGHTS_STR;
+use super::settings::CustomRafsiMaps;
+use crate::error::VlazbaError;
+use crate::gismu_utils::{is_lojban_candidate, GismuCandidate, GismuMatcher, GismuScorer, SimilarGismu};
+use crate::jvozba::dictionary::RafsiDictionary;
+use crate::jvozba::jvokaha::{jvokaha_decomposition, LujvoDecomposition, LujvoPartKind};
+use crate::jvozba::rafsi_list::RafsiMap;
+use crate::jvozba::scoring::{scorer_by_name, Cll1994};
+use crate::jvozba::tools::{try_reconstruct_lujvo, RafsiOptions, ReconstructedLujvo};
+use crate::jvozba::{try_jvozba, JvozbaLimits, LujvoAndScore};
+
+/// Limits on what one request may ask for
+#[derive(Debug, Clone)]
+pub struct ServerLimits {
+    /// Maximum number of words in a tanru
+    pub max_tanru_words: usize,
+    /// Maximum number of rafsi combinations jvozba may score for one tanru
+    pub max_candidates: u64,
+    /// Maximum time jvozba may spend on one tanru
+    pub timeout: Duration,
+    /// Maximum number of gismu candidates to score in one request
+    pub max_gismu_candidates: usize,
+    /// Maximum size of a request body in bytes
+    pub max_body_bytes: usize,
+}
+
+impl Default for ServerLimits {
+    fn default() -> Self {
+        ServerLimits {
+            max_tanru_words: 8,
+            max_candidates: 1_000_000,
+            timeout: Duration::from_secs(2),
+            max_gismu_candidates: 1000,
+            max_body_bytes: 1 << 20,
+        }
+    }
+}
+
+/// A JSON response: the HTTP status and the body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    fn json(value: &impl Serialize) -> Self {
+        match serde_json::to_string(value) {
+            Ok(body) => Response { status: 200, body },
+            Err(e) => Response::error(500, e),
+        }
+    }
+
+    fn error(status: u16, message: impl ToString) -> Self {
+        Response {
+            status,
+            body: serde_json::json!({ "error": message.to_string() }).to_string(),
+        }
+    }
+}
+
+impl From<VlazbaError> for Response {
+    fn from(e: VlazbaError) -> Self {
+        match e {
+            VlazbaError::SearchLimit(_) => Response::error(413, e),
+            _ => Response::error(422, e),
+        }
+    }
+}
+
+/// Rafsi settings a request may override, as in [`RafsiOptions`]
+///
+/// A custom map given here replaces the server's map of the same kind.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RafsiOverrides {
+    exp_rafsi: Option<bool>,
+    custom_gismu: Option<RafsiMap>,
+    custom_gismu_exp: Option<RafsiMap>,
+    custom_cmavo: Option<RafsiMap>,
+    custom_cmavo_exp: Option<RafsiMap>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JvozbaRequest {
+    /// Words of the tanru, separated by spaces
+    tanru: String,
+    #[serde(default)]
+    forbid_la_lai_doi: bool,
+    #[serde(default)]
+    scorer: Option<String>,
+    #[serde(default)]
+    explain: bool,
+    #[serde(default)]
+    options: RafsiOverrides,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LujvoRequest {
+    lujvo: String,
+    #[serde(default)]
+    forbid_cmevla: bool,
+    #[serde(default)]
+    options: RafsiOverrides,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GimkaRequest {
+    candidate: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GismuScoreRequest {
+    /// Source-language words, separated by spaces, in the order of the weights
+    words: String,
+    /// Weights as for `--weights`
+    #[serde(default)]
+    weights: Option<String>,
+    candidates: Vec<String>,
+}
+
+/// Answers JSON requests for jvozba, jvokaha, lujvo reconstruction, gimka and gismu scoring
+///
+/// The rafsi dictionaries are built once, when the server is created; only requests
+/// with custom rafsi maps get a dictionary of their own.
+///
+/// | Endpoint | Request | Response |
+/// |---|---|---|
+/// | `POST /jvozba` | `{"tanru": "klama gasnu"}` | lujvo, best first |
+/// | `POST /jvokaha` | `{"lujvo": "klagau"}` | rafsi and selrafsi |
+/// | `POST /reconstruct` | `{"lujvo": "klagasnu"}` | the canonical lujvo |
+/// | `POST /gimka` | `{"candidate": "bakta"}` | similar existing gismu |
+/// | `POST /gismu-score` | `{"words": "...", "candidates": ["..."]}` | scored candidates, best first |
+///
+/// The lujvo endpoints also take `"options"` with `exp_rafsi` and custom rafsi maps.
+pub struct Server {
+    maps: CustomRafsiMaps,
+    exp_rafsi: bool,
+    dictionary: RafsiDictionary,
+    dictionary_exp: RafsiDictionary,
+    matcher: GismuMatcher<'static>,
+    limits: ServerLimits,
+}
+
+impl Server {
+    /// `exp_rafsi` is used by requests that do not choose
+    pub fn new(maps: CustomRafsiMaps, exp_rafsi: bool, gismu_list: Vec<String>, limits: ServerLimits) -> Self {
+        Server {
+            dictionary: RafsiDictionary::from_options(&maps.options(false)),
+            dictionary_exp: RafsiDictionary::from_options(&maps.options(true)),
+            maps,
+            exp_rafsi,
+            matcher: GismuMatcher::owned(gismu_list, None),
+            limits,
+        }
+    }
+
+    /// Answer one request
+    pub fn handle(&self, method: &str, path: &str, body: &str) -> Response {
+        let path = path.split('?').next().unwrap_or_default();
+        let endpoint: fn(&Self, &str) -> Response = match path {
+            "/jvozba" => Self::jvozba,
+            "/jvokaha" => Self::jvokaha,
+            "/reconstruct" => Self::reconstruct,
+            "/gimka" => Self::gimka,
+            "/gismu-score" => Self::gismu_score,
+            _ => return Response::error(404, format!("no endpoint {}", path)),
+        };
+        if method != "POST" {
+            return Response::error(405, format!("{} takes POST requests", path));
+        }
+        if body.len() > self.limits.max_body_bytes {
+            return Response::error(413, "request body too large");
+        }
+        endpoint(self, body)
+    }
+
+    /// Listen on `addr` and answer requests on `threads` threads until the process ends
+    pub fn serve(&self, addr: &str, threads: usize) -> io::Result<()> {
+        let server = tiny_http::Server::http(addr).map_err(io::Error::other)?;
+        let content_type = tiny_http::Header::from_bytes("Content-Type", "application/json").unwrap();
+        thread::scope(|scope| {
+            for _ in 0..threads.max(1) {
+                scope.spawn(|| {
+                    for mut request in server.incoming_requests() {
+                        let mut body = String::new();
+                        let response = match request
+                            .as_reader()
+                            .take(self.limits.max_body_bytes as u64 + 1)
+                            .read_to_string(&mut body)
+                        {
+                            Ok(_) => self.handle(request.method().as_str(), request.url(), &body),
+                            Err(e) => Response::error(400, e),
+                        };
+                        let response = tiny_http::Response::from_string(response.body)
+                            .with_status_code(response.status)
+                            .with_header(content_type.clone());
+                        // The client may be gone; that only concerns this request
+                        let _ = request.respond(response);
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
+    /// The rafsi dictionary for a request's overrides
+    fn dictionary(&self, overrides: &RafsiOverrides) -> Cow<'_, RafsiDictionary> {
+        let exp_rafsi = overrides.exp_rafsi.unwrap_or(self.exp_rafsi);
+        let custom = overrides.custom_gismu.is_some()
+            || overrides.custom_cmavo.is_some()
+            || (exp_rafsi && (overrides.custom_gismu_exp.is_some() || overrides.custom_cmavo_exp.is_some()));
+        if !custom {
+            return Cow::Borrowed(if exp_rafsi { &self.dictionary_exp } else { &self.dictionary });
+        }
+        Cow::Owned(RafsiDictionary::from_options(&RafsiOptions {
+            exp_rafsi,
+            custom_cmavo: overrides.custom_cmavo.as_ref().or(self.maps.cmavo.as_ref()),
+            custom_cmavo_exp: overrides.custom_cmavo_exp.as_ref().or(self.maps.cmavo_exp.as_ref()),
+            custom_gismu: overrides.custom_gismu.as_ref().or(self.maps.gismu.as_ref()),
+            custom_gismu_exp: overrides.custom_gismu_exp.as_ref().or(self.maps.gismu_exp.as_ref()),
+        }))
+    }
+
+    fn jvozba_limits(&self) -> JvozbaLimits {
+        JvozbaLimits {
+            max_components: Some(self.limits.max_tanru_words),
+            max_candidates: Some(self.limits.max_candidates),
+            timeout: Some(self.limits.timeout),
+        }
+    }
+
+    fn jvozba(&self, body: &str) -> Response {
+        let request: JvozbaRequest = match parse(body) {
+            Ok(request) => request,
+            Err(response) => return response,
+        };
+        let scorer = match scorer_by_name(request.scorer.as_deref().unwrap_or("cll")) {
+            Some(scorer) => scorer,
+            None => return Response::error(400, "unknown scorer"),
+        };
+        let words: Vec<String> = request.tanru.split_whitespace().map(|word| word.to_string()).collect();
+        if words.len() < 2 {
+            return VlazbaError::TooFewValsi { count: words.len() }.into();
+        }
+        let dictionary = self.dictionary(&request.options);
+        let limits = self.jvozba_limits();
+        match try_jvozba(&words, request.forbid_la_lai_doi, false, dictionary.as_ref(), scorer.as_ref(), &limits) {
+            Ok(results) if request.explain => {
+                let results: Vec<LujvoAndScore> = results.into_iter().map(|result| result.explained()).collect();
+                Response::json(&results)
+            }
+            Ok(results) => Response::json(&results),
+            Err(e) => e.into(),
+        }
+    }
+
+    fn jvokaha(&self, body: &str) -> Response {
+        let request: LujvoRequest = match parse(body) {
+            Ok(request) => request,
+            Err(response) => return response,
+        };
+        let dictionary = self.dictionary(&request.options);
+        let result: Result<LujvoDecomposition, _> = jvokaha_decomposition(&request.lujvo, dictionary.as_ref());
+        match result {
+            Ok(decomposition) => Response::json(&decomposition),
+            Err(e) => e.into(),
+        }
+    }
+
+    fn reconstruct(&self, body: &str) -> Response {
+        let request: LujvoRequest = match parse(body) {
+            Ok(request) => request,
+            Err(response) => return response,
+        };
+        let dictionary = self.dictionary(&request.options);
+        let limits = self.jvozba_limits();
+        match try_reconstruct_lujvo(&request.lujvo, request.forbid_cmevla, dictionary.as_ref(), &Cll1994, &limits) {
+            Ok(reconstructed) => Response::json(&ReconstructedLujvo {
+                lujvo: request.lujvo,
+                reconstructed,
+            }),
+            Err(e) => e.into(),
+        }
+    }
+
+    fn gimka(&self, body: &str) -> Response {
+        let request: GimkaRequest = match parse(body) {
+            Ok(request) => request,
+            Err(response) => return response,
+        };
+        if !is_lojban_candidate(&request.candidate) {
+            return Response::error(400, format!("{} is not made of Lojban letters", request.candidate));
+        }
+        Response::json(&SimilarGismu {
+            similar: self.matcher.gimka(&request.candidate),
+            candidate: request.candidate,
+        })
+    }
+
+    fn gismu_score(&self, body: &str) -> Response {
+        let request: GismuScoreRequest = match parse(body) {
+            Ok(request) => request,
+            Err(response) => return response,
+        };
+        if request.candidates.len() > self.limits.max_gismu_candidates {
+            return Response::error(
+                413,
+                format!("more than {} candidates", self.limits.max_gismu_candidates),
+            );
+        }
+        if let Some(candidate) = request
+            .candidates
+            .iter()
+            .find(|c| !matches!(LujvoPartKind::from_rafsi(c), Some(LujvoPartKind::Ccvcv | LujvoPartKind::Cvccv)))
+        {
+            return Response::error(400, format!("{} is not shaped like a gismu", candidate));
+        }
+        let words: Vec<String> = request.words.split_whitespace().map(|word| word.to_string()).collect();
+        let weights = match generate_weights(request.weights.as_deref().unwrap_or(DEFAULT_WEIGHTS_STR.as_str()))
+            .and_then(|weights| validate_words(&words, &weights).map(|_| weights))
+        {
+            Ok(weights) => weights,
+            Err(e) => return Response::error(400, e),
+        };
+        let scorer = GismuScorer::new(&words, &weights);
+        let mut scores: Vec<GismuCandidate> =
+            request.candidates.iter().map(|candidate| scorer.score_candidate(candidate)).collect();
+        scores.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Response::json(&scores)
+    }
+}
+
+fn parse<'a, T: Deserialize<'a>>(body: &'a str) -> Result<T, Response> {
+    serde_json::from_str(body).map_err(|e| Response::error(400, format!("invalid request: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn server() -> Server {
+        let gismu_list = ["bakni", "klama", "gasnu"].map(String::from).to_vec();
+        Server::new(CustomRafsiMaps::default(), false, gismu_list, ServerLimits::default())
+    }
+
+    fn post(server: &Server, path: &str, body: &str) -> (u16, Value) {
+        let response = server.handle("POST", path, body);
+        (response.status, serde_json::from_str(&response.body).unwrap())
+    }
+
+    #[test]
+    fn test_lujvo_endpoints() {
+        let server = server();
+        let (status, json) = post(&server, "/jvozba", r#"{"tanru": "klama gasnu"}"#);
+        assert_eq!(status, 200);
+        assert_eq!(json[0]["lujvo"], "klagau");
+        let (status, json) = post(&server, "/jvokaha", r#"{"lujvo": "klagau"}"#);
+        assert_eq!((status, &json["selrafsi"][1]), (200, &Value::from("gasnu")));
+        let (status, json) = post(&server, "/reconstruct", r#"{"lujvo": "klagasnu"}"#);
+        assert_eq!((status, &json["reconstructed"]), (200, &Value::from("klagau")));
+        let (status, json) = post(&server, "/jvokaha", r#"{"lujvo": "klagaux"}"#);
+        assert_eq!(status, 422);
+        assert!(json["error"].is_string());
+    }
+
+    #[test]
+    fn test_overrides_and_limits() {
+        let server = server();
+        let (_, json) = post(
+            &server,
+            "/jvozba",
+            r#"{"tanru": "klama gasnu", "options": {"custom_gismu": {"klama": ["kam"], "gasnu": ["gau"]}}}"#,
+        );
+        assert_eq!(json[0]["lujvo"], "kamgau");
+        let tanru = ["klama"; 9].join(" ");
+        let (status, _) = post(&server, "/jvozba", &format!(r#"{{"tanru": "{}"}}"#, tanru));
+        assert_eq!(status, 413);
+        let lujvo = format!("{}gau", "kla".repeat(9));
+        let (status, _) = post(&server, "/reconstruct", &format!(r#"{{"lujvo": "{}"}}"#, lujvo));
+        assert_eq!(status, 413);
+        let (status, _) = post(&server, "/jvozba", r#"{"tanru": "klama gasnu", "colour": 1}"#);
+        assert_eq!(status, 400);
+        assert_eq!(server.handle("GET", "/jvozba", "").status, 405);
+        assert_eq!(server.handle("POST", "/nothing", "").status, 404);
+    }
+
+    #[test]
+    fn test_gismu_endpoints() {
+        let server = server();
+        let (status, json) = post(&server, "/gimka", r#"{"candidate": "bakna"}"#);
+        assert_eq!((status, &json["similar"][0]), (200, &Value::from("bakni")));
+        let (status, _) = post(&server, "/gimka", r#"{"candidate": "kléma"}"#);
+        assert_eq!(status, 400);
+        let (status, json) = post(
+            &server,
+            "/gismu-score",
+            r#"{"words": "uan rakan ekspekt esper predpologa mulud", "candidates": ["kamba", "rakpe"]}"#,
+        );
+        assert_eq!(status, 200);
+        assert!(json[0]["score"].as_f64() >= json[1]["score"].as_f64());
+        let (status, _) = post(&server, "/gismu-score", r#"{"words": "uan rakan", "candidates": ["ka"]}"#);
+        assert_eq!(status, 400);
+    }
+}
@@ -0,0 +1,212 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::jvozba::rafsi_list::RafsiMap;
+use crate::jvozba::tools::RafsiOptions;
+
+/// Name of the config file looked up by [`Settings::find`]
+pub const CONFIG_FILE_NAME: &str = "vlazba.toml";
+
+/// Error reading a config file or a custom rafsi map
+#[derive(Debug)]
+pub enum SettingsError {
+    Io { path: PathBuf, source: io::Error },
+    Json { path: PathBuf, source: serde_json::Error },
+    Toml { path: PathBuf, source: toml::de::Error },
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Io { path, .. } => write!(f, "Failed to read {}", path.display()),
+            SettingsError::Json { path, .. } => write!(f, "Invalid JSON in {}", path.display()),
+            SettingsError::Toml { path, .. } => write!(f, "Invalid TOML in {}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SettingsError::Io { source, .. } => Some(source),
+            SettingsError::Json { source, .. } => Some(source),
+            SettingsError::Toml { source, .. } => Some(source),
+        }
+    }
+}
+
+/// Defaults read from a `vlazba.toml` config file
+///
+/// ```toml
+/// exp_rafsi = true
+/// weights = "1995"
+/// shapes = "ccvcv,cvccv"
+/// custom_gismu_exp = "community-rafsi.toml"
+/// ```
+///
+/// Relative paths are relative to the directory of the config file.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Also use experimental rafsi
+    pub exp_rafsi: bool,
+    /// Weights of the source languages for gismu generation, as for `--weights`
+    pub weights: Option<String>,
+    /// Shapes of gismu candidates, as for `--shapes`
+    pub shapes: Option<String>,
+    pub custom_gismu: Option<PathBuf>,
+    pub custom_gismu_exp: Option<PathBuf>,
+    pub custom_cmavo: Option<PathBuf>,
+    pub custom_cmavo_exp: Option<PathBuf>,
+}
+
+impl Settings {
+    /// Read a config file
+    pub fn load(path: &Path) -> Result<Self, SettingsError> {
+        let text = read(path)?;
+        let mut settings: Settings = toml::from_str(&text).map_err(|source| SettingsError::Toml {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        if let Some(dir) = path.parent() {
+            for map_path in [
+                &mut settings.custom_gismu,
+                &mut settings.custom_gismu_exp,
+                &mut settings.custom_cmavo,
+                &mut settings.custom_cmavo_exp,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                if map_path.is_relative() {
+                    *map_path = dir.join(&*map_path);
+                }
+            }
+        }
+        Ok(settings)
+    }
+
+    /// The config file to use when none is given
+    ///
+    /// A `vlazba.toml` in the current directory comes first, then
+    /// `$XDG_CONFIG_HOME/vlazba/vlazba.toml`, or `~/.config/vlazba/vlazba.toml`
+    /// without `XDG_CONFIG_HOME`.
+    pub fn find() -> Option<PathBuf> {
+        let user_config_dir = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+        let mut candidates = vec![PathBuf::from(CONFIG_FILE_NAME)];
+        if let Some(dir) = user_config_dir {
+            candidates.push(dir.join("vlazba").join(CONFIG_FILE_NAME));
+        }
+        candidates.into_iter().find(|path| path.is_file())
+    }
+}
+
+/// Read a rafsi map from a JSON file, or from a TOML file if its name ends in `.toml`
+///
+/// Both have the layout of the bundled lists, a table from each word to its rafsi.
+pub fn load_rafsi_map(path: &Path) -> Result<RafsiMap, SettingsError> {
+    let text = read(path)?;
+    if path.extension().is_some_and(|ext| ext == "toml") {
+        toml::from_str(&text).map_err(|source| SettingsError::Toml {
+            path: path.to_path_buf(),
+            source,
+        })
+    } else {
+        serde_json::from_str(&text).map_err(|source| SettingsError::Json {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
+
+/// Custom rafsi maps loaded from files, to be used through [`CustomRafsiMaps::options`]
+#[derive(Debug, Clone, Default)]
+pub struct CustomRafsiMaps {
+    pub gismu: Option<RafsiMap>,
+    pub gismu_exp: Option<RafsiMap>,
+    pub cmavo: Option<RafsiMap>,
+    pub cmavo_exp: Option<RafsiMap>,
+}
+
+impl CustomRafsiMaps {
+    /// Load the maps whose paths are given, in the order gismu, gismu_exp, cmavo, cmavo_exp
+    pub fn load(paths: [Option<&Path>; 4]) -> Result<Self, SettingsError> {
+        let [gismu, gismu_exp, cmavo, cmavo_exp] = paths.map(|path| path.map(load_rafsi_map));
+        Ok(CustomRafsiMaps {
+            gismu: gismu.transpose()?,
+            gismu_exp: gismu_exp.transpose()?,
+            cmavo: cmavo.transpose()?,
+            cmavo_exp: cmavo_exp.transpose()?,
+        })
+    }
+
+    pub fn options(&self, exp_rafsi: bool) -> RafsiOptions<'_> {
+        RafsiOptions {
+            exp_rafsi,
+            custom_cmavo: self.cmavo.as_ref(),
+            custom_cmavo_exp: self.cmavo_exp.as_ref(),
+            custom_gismu: self.gismu.as_ref(),
+            custom_gismu_exp: self.gismu_exp.as_ref(),
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, SettingsError> {
+    fs::read_to_string(path).map_err(|source| SettingsError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_settings_and_maps() {
+        let dir = env::temp_dir().join(format!("vlazba-settings-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("extra.toml"), "zviki = [\"zvi\"]\n\"xo'ei\" = [\"xoz\"]\n").unwrap();
+        fs::write(dir.join("extra.json"), "{\"klama\": [\"kam\"]}").unwrap();
+        fs::write(
+            dir.join(CONFIG_FILE_NAME),
+            "exp_rafsi = true\nweights = \"1995\"\ncustom_gismu_exp = \"extra.toml\"\ncustom_cmavo = \"extra.json\"\n",
+        )
+        .unwrap();
+
+        let settings = Settings::load(&dir.join(CONFIG_FILE_NAME)).unwrap();
+        assert!(settings.exp_rafsi);
+        assert_eq!(settings.weights.as_deref(), Some("1995"));
+        assert_eq!(settings.custom_gismu_exp, Some(dir.join("extra.toml")));
+
+        let maps = CustomRafsiMaps::load([
+            None,
+            settings.custom_gismu_exp.as_deref(),
+            settings.custom_cmavo.as_deref(),
+            None,
+        ])
+        .unwrap();
+        assert_eq!(maps.gismu_exp.as_ref().unwrap()["xo'ei"], vec!["xoz"]);
+        assert_eq!(maps.cmavo.as_ref().unwrap()["klama"], vec!["kam"]);
+        let options = maps.options(true);
+        assert!(options.custom_gismu.is_none() && options.custom_gismu_exp.is_some());
+
+        fs::write(dir.join("bad.toml"), "colour = \"blue\"\n").unwrap();
+        assert!(matches!(
+            Settings::load(&dir.join("bad.toml")),
+            Err(SettingsError::Toml { .. })
+        ));
+        assert!(matches!(
+            load_rafsi_map(&dir.join("missing.json")),
+            Err(SettingsError::Io { .. })
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
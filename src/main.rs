@@ -1,231 +1,777 @@
-use clap::{Arg, Command};
-use jvozba::{jvokaha, jvozba, tools::{search_selrafsi_from_rafsi2, RafsiOptions}};
+use clap::{parser::ValueSource, Arg, ArgAction, ArgMatches, Command};
+use once_cell::unsync::OnceCell;
 use rayon::prelude::*;
 use std::{
     collections::HashSet,
     fs::File,
     io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::ExitCode,
     sync::Arc,
+    time::Duration,
+};
+use vlazba::{
+    error::VlazbaError,
+    gismu_utils::{GismuCandidate, GismuGenerator, GismuMatcher, GismuScorer, SimilarGismu},
+    jvozba::{
+        self,
+        audit::audit_rafsi,
+        dictionary::RafsiDictionary,
+        inventory::{free_rafsi, FreeRafsiFilter},
+        jbovlaste::import_jbovlaste,
+        jvokaha::{jvokaha_decomposition, LujvoPartKind},
+        recommend::{exp_rafsi_entry, recommend_rafsi, COMMON_PARTNERS},
+        scoring::{scorer_by_name, SCORER_NAMES},
+        tools::{RafsiOptions, ReconstructedLujvo},
+        try_jvozba, JvozbaLimits, LujvoAndScore,
+    },
+    libs::{
+        cli::{generate_weights, validate_words},
+        config::{C, DEFAULT_WEIGHTS_STR, V, VERSION},
+        settings::{CustomRafsiMaps, Settings, SettingsError},
+        output::{write_records, LineRecord, OutputFormat, Record, FORMAT_NAMES},
+    },
 };
-use smallvec::SmallVec;
-
-mod libs;
-use libs::{cli::{generate_weights, validate_words}, config::{C, DEFAULT_WEIGHTS_STR, V, VERSION}};
-
-mod gismu_utils;
-use gismu_utils::{GismuGenerator, GismuMatcher, GismuScorer};
-mod jvozba;
 
 fn log(msg: &str) {
     eprintln!("{}", msg);
 }
 
-fn main() -> anyhow::Result<()> {
-    let matches = Command::new("Optimized Gismu Generator")
+/// Why the CLI failed, which decides its exit code
+#[derive(Debug)]
+enum CliError {
+    /// Nothing matched valid input (exit code 1)
+    NoResult(String),
+    /// The input or options are invalid (exit code 2, as for clap usage errors)
+    InvalidInput(anyhow::Error),
+    /// Anything else (exit code 3)
+    Internal(anyhow::Error),
+    /// A check ran and found problems (exit code 4)
+    IssuesFound(String),
+}
+
+impl CliError {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            CliError::NoResult(_) => ExitCode::from(1),
+            CliError::InvalidInput(_) => ExitCode::from(2),
+            CliError::Internal(_) => ExitCode::from(3),
+            CliError::IssuesFound(_) => ExitCode::from(4),
+        }
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CliError::NoResult(msg) | CliError::IssuesFound(msg) => write!(f, "{}", msg),
+            CliError::InvalidInput(e) => write!(f, "Invalid input: {:#}", e),
+            CliError::Internal(e) => write!(f, "Error: {:#}", e),
+        }
+    }
+}
+
+impl From<VlazbaError> for CliError {
+    fn from(e: VlazbaError) -> Self {
+        match e {
+            VlazbaError::NoLujvo { .. } => CliError::NoResult(e.to_string()),
+            _ => CliError::InvalidInput(e.into()),
+        }
+    }
+}
+
+impl From<anyhow::Error> for CliError {
+    fn from(e: anyhow::Error) -> Self {
+        CliError::Internal(e)
+    }
+}
+
+impl From<SettingsError> for CliError {
+    fn from(e: SettingsError) -> Self {
+        CliError::InvalidInput(e.into())
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(e: io::Error) -> Self {
+        CliError::Internal(e.into())
+    }
+}
+
+/// What every command shares: the output format, config file defaults and custom rafsi maps
+///
+/// The config file and custom maps are only read by the commands that use them, so that a
+/// broken config does not stop the others.
+struct Context<'a> {
+    matches: &'a ArgMatches,
+    format: OutputFormat,
+    settings: OnceCell<Settings>,
+    maps: OnceCell<CustomRafsiMaps>,
+}
+
+impl<'a> Context<'a> {
+    fn new(matches: &'a ArgMatches) -> Self {
+        let format = matches
+            .get_one::<String>("format")
+            .and_then(|name| OutputFormat::from_name(name))
+            .unwrap_or_default();
+        Context {
+            matches,
+            format,
+            settings: OnceCell::new(),
+            maps: OnceCell::new(),
+        }
+    }
+
+    fn settings(&self) -> Result<&Settings, CliError> {
+        self.settings.get_or_try_init(|| {
+            Ok(match self.matches.get_one::<String>("config") {
+                Some(path) => Settings::load(Path::new(path))?,
+                None => Settings::find()
+                    .map(|path| Settings::load(&path))
+                    .transpose()?
+                    .unwrap_or_default(),
+            })
+        })
+    }
+
+    fn maps(&self) -> Result<&CustomRafsiMaps, CliError> {
+        self.maps.get_or_try_init(|| {
+            let settings = self.settings()?;
+            // Maps given on the command line take the place of those in the config file
+            let path = |id: &str, configured: &Option<PathBuf>| {
+                self.matches
+                    .get_one::<String>(id)
+                    .map(PathBuf::from)
+                    .or_else(|| configured.clone())
+            };
+            let paths = [
+                path("custom_gismu", &settings.custom_gismu),
+                path("custom_gismu_exp", &settings.custom_gismu_exp),
+                path("custom_cmavo", &settings.custom_cmavo),
+                path("custom_cmavo_exp", &settings.custom_cmavo_exp),
+            ];
+            Ok(CustomRafsiMaps::load(paths.each_ref().map(|path| path.as_deref()))?)
+        })
+    }
+
+    /// Whether `--exp-rafsi` or the config file asks for experimental rafsi
+    fn exp_rafsi(&self, m: &ArgMatches) -> Result<bool, CliError> {
+        Ok(m.get_flag("exp_rafsi") || self.settings()?.exp_rafsi)
+    }
+
+    fn options(&self, exp_rafsi: bool) -> Result<RafsiOptions<'_>, CliError> {
+        Ok(self.maps()?.options(exp_rafsi))
+    }
+
+    fn dictionary(&self, exp_rafsi: bool) -> Result<RafsiDictionary, CliError> {
+        Ok(RafsiDictionary::from_options(&self.options(exp_rafsi)?))
+    }
+
+    /// A gismu generation option from the command line, else the config file, else its default
+    fn gismu_option<'b>(&'b self, m: &'b ArgMatches, id: &str, configured: &'b Option<String>) -> &'b str {
+        let from_command_line = m.value_source(id) == Some(ValueSource::CommandLine);
+        match configured {
+            Some(value) if !from_command_line => value,
+            _ => m.get_one::<String>(id).unwrap(),
+        }
+    }
+}
+
+/// Write results to stdout
+///
+/// A reader that stops early, such as `head`, is not an error.
+fn print_records<R: Record>(format: OutputFormat, records: &[R]) -> Result<(), CliError> {
+    match write_records(&mut io::stdout().lock(), format, records) {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Process every non-empty line of a batch in parallel and write one record per line
+///
+/// A line that fails gets a record with its error; the other lines are still processed.
+fn run_batch<R, F>(format: OutputFormat, lines: &[String], process: F) -> Result<(), CliError>
+where
+    R: Record + Send,
+    F: Fn(&str) -> Result<R, CliError> + Sync,
+{
+    let records: Vec<LineRecord<R>> = lines
+        .par_iter()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let input = line.trim();
+            LineRecord::new(index + 1, input, process(input).map_err(|e| e.to_string()))
+        })
+        .collect();
+    print_records(format, &records)?;
+    let failed = records.iter().filter(|record| record.error.is_some()).count();
+    log(&format!("{} lines processed, {} failed", records.len(), failed));
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            log(&e.to_string());
+            e.exit_code()
+        }
+    }
+}
+
+/// An option shared by several subcommands and the deprecated top-level flags
+fn flag(id: &'static str, long: &'static str, help: &'static str) -> Arg {
+    Arg::new(id)
+        .long(long)
+        .help(help)
+        .num_args(0)
+        .action(ArgAction::SetTrue)
+}
+
+fn words_arg(value_name: &'static str, help: &'static str) -> Arg {
+    Arg::new("words").value_name(value_name).help(help)
+}
+
+fn batch_arg() -> Arg {
+    Arg::new("batch")
+        .long("batch")
+        .value_name("FILE")
+        .help("Read one input per line from a file, or from stdin for -")
+}
+
+fn exp_rafsi_arg() -> Arg {
+    flag("exp_rafsi", "exp-rafsi", "Also use experimental rafsi")
+}
+
+fn gismu_args() -> [Arg; 4] {
+    [
+        Arg::new("all-letters")
+            .short('a')
+            .long("all-letters")
+            .help("Use all letters")
+            .num_args(0)
+            .action(ArgAction::SetTrue),
+        Arg::new("shapes")
+            .short('s')
+            .long("shapes")
+            .default_value("ccvcv,cvccv")
+            .help("Shapes for gismu candidates"),
+        Arg::new("weights")
+            .short('w')
+            .long("weights")
+            .default_value(DEFAULT_WEIGHTS_STR.as_str())
+            .help("Weights for input words"),
+        Arg::new("deduplicate")
+            .short('d')
+            .long("deduplicate")
+            .help("Path to gismu list for deduplication"),
+    ]
+}
+
+fn lujvo_args() -> [Arg; 7] {
+    [
+        Arg::new("max_components")
+            .long("max-components")
+            .help("Give up on input with more components than this")
+            .value_parser(clap::value_parser!(usize)),
+        Arg::new("max_candidates")
+            .long("max-candidates")
+            .help("Give up on input with more rafsi combinations than this")
+            .value_parser(clap::value_parser!(u64)),
+        Arg::new("timeout_ms")
+            .long("timeout-ms")
+            .help("Give up after this many milliseconds")
+            .value_parser(clap::value_parser!(u64)),
+        Arg::new("scorer")
+            .long("scorer")
+            .help("How to rank the lujvo")
+            .value_parser(SCORER_NAMES)
+            .default_value("cll"),
+        flag("explain", "explain", "Show how each score is made up"),
+        flag("forbid_la_lai_doi", "forbid-la-lai-doi", "Forbid la, lai, doi in lujvo"),
+        exp_rafsi_arg(),
+    ]
+}
+
+fn free_rafsi_args() -> [Arg; 2] {
+    [
+        Arg::new("shape")
+            .long("shape")
+            .help("Shapes of rafsi to list")
+            .value_delimiter(',')
+            .value_parser(["cvc", "ccv", "cvv", "cv'v"]),
+        Arg::new("pattern")
+            .long("pattern")
+            .help("Letters of the rafsi, ? standing for any letter, e.g. k?a"),
+    ]
+}
+
+fn custom_map_arg(id: &'static str, long: &'static str, kind: &str) -> Arg {
+    Arg::new(id)
+        .long(long)
+        .value_name("FILE")
+        .help(format!("JSON or TOML file of {} to use instead of the bundled ones", kind))
+        .global(true)
+}
+
+fn data_dir_arg() -> Arg {
+    Arg::new("data_dir")
+        .long("data-dir")
+        .help("Source directory to write the data files to")
+        .default_value("src")
+}
+
+fn cli() -> Command {
+    // The flags before subcommands existed still work, hidden from the help
+    let legacy = |arg: Arg| arg.hide(true);
+    let command = Command::new("Optimized Gismu Generator")
         .version(VERSION)
-        .arg(Arg::new("words").help("Input words"))
+        .about("Lojban words generator and analyzer")
+        .arg_required_else_help(true)
         .arg(
-            Arg::new("all-letters")
-                .short('a')
-                .long("all-letters")
-                .help("Use all letters"),
+            Arg::new("format")
+                .long("format")
+                .help("Output format of the results")
+                .value_parser(FORMAT_NAMES)
+                .default_value("text")
+                .global(true),
         )
         .arg(
-            Arg::new("shapes")
-                .short('s')
-                .long("shapes")
-                .default_value("ccvcv,cvccv")
-                .help("Shapes for gismu candidates"),
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .help("Config file to use instead of ./vlazba.toml or ~/.config/vlazba/vlazba.toml")
+                .global(true),
         )
-        .arg(
-            Arg::new("weights")
-                .short('w')
-                .long("weights")
-                .default_value(DEFAULT_WEIGHTS_STR.as_str())
-                .help("Weights for input words"),
+        .args([
+            custom_map_arg("custom_gismu", "custom-gismu", "gismu rafsi"),
+            custom_map_arg("custom_gismu_exp", "custom-gismu-exp", "experimental gismu rafsi"),
+            custom_map_arg("custom_cmavo", "custom-cmavo", "cmavo rafsi"),
+            custom_map_arg("custom_cmavo_exp", "custom-cmavo-exp", "experimental cmavo rafsi"),
+        ])
+        .subcommand(
+            Command::new("gismu")
+                .about("Generate gismu candidates from source-language words")
+                .arg(words_arg("WORDS", "Source-language words, in the order of the weights").required(true))
+                .args(gismu_args()),
         )
-        .arg(
-            Arg::new("deduplicate")
-                .short('d')
-                .long("deduplicate")
-                .help("Path to gismu list for deduplication"),
+        .subcommand(
+            Command::new("lujvo")
+                .about("Make lujvo from a tanru with jvozba")
+                .arg(words_arg("TANRU", "Words of the tanru").required_unless_present("batch"))
+                .args(lujvo_args())
+                .arg(batch_arg()),
         )
-        .arg(
-            Arg::new("jvozba")
-                .long("jvozba")
-                .help("Use jvozba function instead of gismu generation")
-                .num_args(0)
-                .action(clap::ArgAction::SetTrue),
+        .subcommand(
+            Command::new("split")
+                .about("Split a lujvo into its selrafsi with jvokaha")
+                .arg(words_arg("LUJVO", "Lujvo to split").required_unless_present("batch"))
+                .arg(exp_rafsi_arg())
+                .arg(batch_arg()),
         )
-        .arg(
-            Arg::new("forbid_la_lai_doi")
-                .long("forbid-la-lai-doi")
-                .help("Forbid la, lai, doi in lujvo")
-                .num_args(0)
-                .action(clap::ArgAction::SetTrue),
+        .subcommand(
+            Command::new("reconstruct")
+                .about("Rebuild the canonical form of a lujvo from its components")
+                .arg(words_arg("LUJVO", "Lujvo to reconstruct").required_unless_present("batch"))
+                .arg(exp_rafsi_arg())
+                .arg(flag("forbid_cmevla", "forbid-cmevla", "Forbid cmevla (name words)"))
+                .arg(batch_arg()),
         )
-        .arg(
-            Arg::new("exp_rafsi")
-                .long("exp-rafsi")
-                .help("All experimental rafsi when generating lujvo")
-                .num_args(0)
-                .action(clap::ArgAction::SetTrue),
+        .subcommand(
+            Command::new("similar")
+                .about("Find existing gismu too similar to a candidate, with gimka")
+                .arg(words_arg("CANDIDATE", "Gismu candidate").required_unless_present("batch"))
+                .arg(
+                    Arg::new("gismu_list")
+                        .long("gismu-list")
+                        .value_name("FILE")
+                        .help("Gismu list to compare with")
+                        .default_value("src/gismu-list.txt"),
+                )
+                .arg(batch_arg()),
         )
-        .arg(
-            Arg::new("jvokaha")
-                .long("jvokaha")
-                .help("Use jvokaha function to split lujvo")
-                .num_args(0)
-                .action(clap::ArgAction::SetTrue),
+        .subcommand(
+            Command::new("free-rafsi")
+                .about("List short rafsi that no word has yet")
+                .args(free_rafsi_args()),
         )
-        .arg(
-            Arg::new("reconstruct")
-                .long("reconstruct")
-                .help("Reconstruct a lujvo from its components")
-                .num_args(0)
-                .action(clap::ArgAction::SetTrue),
+        .subcommand(
+            Command::new("recommend-rafsi")
+                .about("Suggest free rafsi for a gismu, ranked by how much they improve common lujvo")
+                .arg(Arg::new("gismu").required(true)),
+        )
+        .subcommand(
+            Command::new("audit-rafsi").about("Check the rafsi lists for conflicting and malformed rafsi"),
         )
+        .subcommand(
+            Command::new("import-jbovlaste")
+                .about("Regenerate the rafsi JSON files and gismu list from a jbovlaste XML export")
+                .arg(Arg::new("xml").value_name("XML").required(true))
+                .arg(data_dir_arg()),
+        )
+        .arg(legacy(words_arg("WORDS", "Input words")))
+        .args(gismu_args().map(legacy))
+        .args(lujvo_args().map(legacy))
+        .args(free_rafsi_args().map(legacy))
+        .arg(legacy(flag("jvozba", "jvozba", "Use jvozba function instead of gismu generation")))
+        .arg(legacy(flag("jvokaha", "jvokaha", "Use jvokaha function to split lujvo")))
+        .arg(legacy(flag("reconstruct", "reconstruct", "Reconstruct a lujvo from its components")))
+        .arg(legacy(flag("forbid_cmevla", "forbid-cmevla", "Forbid cmevla (name words) in lujvo reconstruction")))
+        .arg(legacy(flag("gimka", "gimka", "Find similar existing gismu using gimka function")))
+        .arg(legacy(flag("free_rafsi", "free-rafsi", "List short rafsi that no word has yet")))
+        .arg(legacy(flag("audit_rafsi", "audit-rafsi", "Check the rafsi lists for conflicting and malformed rafsi")))
+        .arg(legacy(batch_arg()))
+        .arg(legacy(
+            Arg::new("recommend_rafsi")
+                .long("recommend-rafsi")
+                .value_name("GISMU")
+                .help("Suggest free rafsi for a gismu"),
+        ))
+        .arg(legacy(
+            Arg::new("import_jbovlaste")
+                .long("import-jbovlaste")
+                .value_name("XML")
+                .help("Regenerate the rafsi data from a jbovlaste XML export"),
+        ))
+        .arg(legacy(data_dir_arg()));
+    #[cfg(feature = "server")]
+    let command = command.subcommand(serve_subcommand());
+    command
+}
+
+#[cfg(feature = "server")]
+fn serve_subcommand() -> Command {
+    let defaults = vlazba::libs::server::ServerLimits::default();
+    // Limits are optional and default to those of `ServerLimits`
+    let number = |id: &'static str, long: &'static str, help: &str, default: String| {
+        Arg::new(id)
+            .long(long)
+            .help(format!("{} [default: {}]", help, default))
+            .value_parser(clap::value_parser!(u64))
+    };
+    Command::new("serve")
+        .about("Answer JSON requests for jvozba, jvokaha, reconstruct, gimka and gismu scoring over HTTP")
         .arg(
-            Arg::new("forbid_cmevla")
-                .long("forbid-cmevla")
-                .help("Forbid cmevla (name words) in lujvo reconstruction")
-                .num_args(0)
-                .action(clap::ArgAction::SetTrue),
+            Arg::new("addr")
+                .long("addr")
+                .help("Address to listen on")
+                .default_value("127.0.0.1:8080"),
         )
+        .arg(number("threads", "threads", "Number of requests answered at once", "4".to_string()))
+        .arg(exp_rafsi_arg().help("Use experimental rafsi for requests that do not choose"))
         .arg(
-            Arg::new("gimka") 
-                .long("gimka")
-                .help("Find similar existing gismu using gimka function")
-                .num_args(0)
-                .action(clap::ArgAction::SetTrue),
+            Arg::new("gismu_list")
+                .long("gismu-list")
+                .value_name("FILE")
+                .help("Gismu list for gimka")
+                .default_value("src/gismu-list.txt"),
         )
-        .get_matches();
+        .arg(number(
+            "max_tanru_words",
+            "max-tanru-words",
+            "Most words a tanru may have",
+            defaults.max_tanru_words.to_string(),
+        ))
+        .arg(number(
+            "max_candidates",
+            "max-candidates",
+            "Most rafsi combinations to score for one tanru",
+            defaults.max_candidates.to_string(),
+        ))
+        .arg(number(
+            "timeout_ms",
+            "timeout-ms",
+            "Most milliseconds to spend on one tanru",
+            defaults.timeout.as_millis().to_string(),
+        ))
+        .arg(number(
+            "max_body_bytes",
+            "max-body-bytes",
+            "Largest request body accepted",
+            defaults.max_body_bytes.to_string(),
+        ))
+}
 
-    if matches.get_flag("jvozba") {
-        let words: Vec<String> = matches
-            .get_one::<String>("words")
-            .map(|s| s.split_whitespace().map(|word| word.to_string()).collect())
-            .unwrap_or_default();
+fn deprecated(flag: &str, subcommand: &str) {
+    log(&format!(
+        "warning: {} is deprecated, use `vlazba {}` instead",
+        flag, subcommand
+    ));
+}
 
-        let forbid_la_lai_doi = matches.get_flag("forbid_la_lai_doi");
-        let exp_rafsi = matches.get_flag("exp_rafsi");
-        let results = jvozba(&words, forbid_la_lai_doi, false, &RafsiOptions {
-            exp_rafsi,
-            custom_cmavo: None,
-            custom_cmavo_exp: None,
-            custom_gismu: None,
-            custom_gismu_exp: None,
-        });
-        for result in results {
-            log(&format!("{}: {}", result.lujvo, result.score));
+fn run() -> Result<(), CliError> {
+    let matches = cli().get_matches();
+    let ctx = Context::new(&matches);
+
+    match matches.subcommand() {
+        Some(("gismu", m)) => return gismu_command(m, &ctx),
+        Some(("lujvo", m)) => return lujvo_command(m, &ctx),
+        Some(("split", m)) => return split_command(m, &ctx),
+        Some(("reconstruct", m)) => return reconstruct_command(m, &ctx),
+        Some(("similar", m)) => {
+            return similar_command(m, &ctx, m.get_one::<String>("gismu_list").unwrap())
+        }
+        Some(("free-rafsi", m)) => return free_rafsi_command(m, &ctx),
+        Some(("recommend-rafsi", m)) => {
+            return recommend_rafsi_command(m.get_one::<String>("gismu").unwrap(), &ctx)
         }
-        return Ok(());
+        Some(("audit-rafsi", _)) => return audit_rafsi_command(&ctx),
+        #[cfg(feature = "server")]
+        Some(("serve", m)) => return serve_command(m, &ctx),
+        Some(("import-jbovlaste", m)) => {
+            return import_jbovlaste_command(
+                m.get_one::<String>("xml").unwrap(),
+                m.get_one::<String>("data_dir").unwrap(),
+            )
+        }
+        _ => {}
     }
 
-    if matches.get_flag("reconstruct") {
-        let lujvo: &str = matches
-            .get_one::<String>("words")
+    // Deprecated flags, checked in the order they always were
+    let m = &matches;
+    if m.get_flag("free_rafsi") {
+        deprecated("--free-rafsi", "free-rafsi");
+        return free_rafsi_command(m, &ctx);
+    }
+    if let Some(gismu) = m.get_one::<String>("recommend_rafsi") {
+        deprecated("--recommend-rafsi", "recommend-rafsi");
+        return recommend_rafsi_command(gismu, &ctx);
+    }
+    if m.get_flag("audit_rafsi") {
+        deprecated("--audit-rafsi", "audit-rafsi");
+        return audit_rafsi_command(&ctx);
+    }
+    if let Some(xml_path) = m.get_one::<String>("import_jbovlaste") {
+        deprecated("--import-jbovlaste", "import-jbovlaste");
+        return import_jbovlaste_command(xml_path, m.get_one::<String>("data_dir").unwrap());
+    }
+    if m.get_flag("jvozba") {
+        deprecated("--jvozba", "lujvo");
+        return lujvo_command(m, &ctx);
+    }
+    if m.get_flag("reconstruct") {
+        deprecated("--reconstruct", "reconstruct");
+        return reconstruct_command(m, &ctx);
+    }
+    if m.get_flag("gimka") {
+        deprecated("--gimka", "similar");
+        let gismu_list_path = m
+            .get_one::<String>("deduplicate")
             .map(String::as_str)
-            .unwrap_or("");
-        let exp_rafsi = matches.get_flag("exp_rafsi");
-
-        let forbid_cmevla = matches.get_flag("forbid_cmevla");
-        match jvozba::tools::reconstruct_lujvo(lujvo, forbid_cmevla, &RafsiOptions {
-            exp_rafsi,
-            custom_cmavo: None,
-            custom_cmavo_exp: None,
-            custom_gismu: None,
-            custom_gismu_exp: None,
-        }) {
-            Ok(reconstructed) => {
-                log(&format!("Reconstructed lujvo: {}", reconstructed));
-            }
-            Err(e) => {
-                log(&format!("Error reconstructing lujvo: {}", e));
-            }
-        }
-        return Ok(());
+            .unwrap_or("src/gismu-list.txt");
+        return similar_command(m, &ctx, gismu_list_path);
+    }
+    if m.get_flag("jvokaha") {
+        deprecated("--jvokaha", "split");
+        return split_command(m, &ctx);
     }
+    if m.contains_id("batch") {
+        return Err(CliError::InvalidInput(anyhow::anyhow!(
+            "--batch works with the lujvo, split, reconstruct and similar subcommands"
+        )));
+    }
+    deprecated("Generating gismu without a subcommand", "gismu");
+    gismu_command(m, &ctx)
+}
 
-    if matches.get_flag("gimka") {
-        let candidate: &str = matches
-            .get_one::<String>("words")
-            .map(String::as_str)
-            .unwrap_or("");
-        let gismu_list_path = matches.get_one::<String>("deduplicate").map(String::as_str).unwrap_or("src/gismu-list.txt");
-        
-        log(&format!("Looking for gismu similar to '{}' using list: {}", candidate, gismu_list_path));
-        let gismus = read_gismu_list(gismu_list_path)?;
-        let matcher = Arc::new(GismuMatcher::new(&gismus, None));
-        let similar = matcher.gimka(candidate);
+/// The positional input of a command
+fn words(m: &ArgMatches) -> &str {
+    m.get_one::<String>("words").map(String::as_str).unwrap_or("")
+}
 
-        if !similar.is_empty() {
-            log("Similar gismu found:");
-            for gismu in similar {
-                log(&format!("- {}", gismu));
-            }
-        } else {
-            log("No similar gismu found");
-        }
-        return Ok(());
+/// The lines of `--batch` input, if given
+fn batch(m: &ArgMatches) -> Result<Option<Vec<String>>, CliError> {
+    m.get_one::<String>("batch").map(|path| read_batch(path)).transpose()
+}
+
+fn free_rafsi_command(m: &ArgMatches, ctx: &Context) -> Result<(), CliError> {
+    let mut filter = FreeRafsiFilter {
+        pattern: m.get_one::<String>("pattern").cloned(),
+        ..Default::default()
+    };
+    if let Some(shapes) = m.get_many::<String>("shape") {
+        filter.shapes = shapes
+            .map(|shape| match shape.as_str() {
+                "cvc" => LujvoPartKind::Cvc,
+                "ccv" => LujvoPartKind::Ccv,
+                "cvv" => LujvoPartKind::Cvv,
+                _ => LujvoPartKind::CvApostropheV,
+            })
+            .collect();
+    }
+    let free = free_rafsi(&ctx.dictionary(true)?, &filter);
+    if free.is_empty() {
+        return Err(CliError::NoResult("No free rafsi found".to_string()));
+    }
+    for rafsi in &free {
+        println!("{}", rafsi);
     }
+    log(&format!("{} free rafsi", free.len()));
+    Ok(())
+}
 
-    if matches.get_flag("jvokaha") {
-        let words: &str = matches
-            .get_one::<String>("words")
-            .map(String::as_str)
-            .unwrap_or("");
-
-        let results = jvokaha::jvokaha(words);
-
-        match results {
-            Ok(result) => {
-                let exp_rafsi = matches.get_flag("exp_rafsi");
-                let arr: Vec<String> = result
-                    .into_iter()
-                    .filter(|a| a.len() > 1)
-                    .map(|rafsi| {
-                        match search_selrafsi_from_rafsi2(&rafsi, &RafsiOptions {
-                            exp_rafsi,
-                            custom_cmavo: None,
-                            custom_cmavo_exp: None,
-                            custom_gismu: None,
-                            custom_gismu_exp: None,
-                        }) {
-                            Some(selrafsi) => selrafsi,
-                            None => format!("-{}-", rafsi), // output as rafsi form; signify as unknown
-                        }
-                    })
-                    .collect();
-                log("Successfully decomposed lujvo:");
-                for (index, rafsi) in arr.iter().enumerate() {
-                    log(&format!("  {}: {}", index + 1, rafsi));
-                }
-            }
-            Err(e) => {
-                log(&format!("Error: {}", e));
-            }
+fn recommend_rafsi_command(gismu: &str, ctx: &Context) -> Result<(), CliError> {
+    let recommendations =
+        recommend_rafsi(gismu, &ctx.dictionary(true)?, COMMON_PARTNERS)?;
+    for r in &recommendations {
+        log(&format!(
+            "{} ({:?}): improves {} lujvo, total gain {}",
+            r.rafsi, r.shape, r.improved, r.gain
+        ));
+    }
+    let useful: Vec<_> = recommendations.into_iter().filter(|r| r.improved > 0).collect();
+    if useful.is_empty() {
+        return Err(CliError::NoResult(format!("No free rafsi would help lujvo with {}", gismu)));
+    }
+    println!("{}", exp_rafsi_entry(gismu, &useful));
+    Ok(())
+}
+
+fn audit_rafsi_command(ctx: &Context) -> Result<(), CliError> {
+    let issues = audit_rafsi(&ctx.options(true)?);
+    for issue in &issues {
+        println!("{}", issue);
+    }
+    if !issues.is_empty() {
+        return Err(CliError::IssuesFound(format!("{} issues found", issues.len())));
+    }
+    log("No issues found");
+    Ok(())
+}
+
+fn import_jbovlaste_command(xml_path: &str, data_dir: &str) -> Result<(), CliError> {
+    let data = import_jbovlaste(Path::new(xml_path))
+        .map_err(|e| CliError::InvalidInput(e.into()))?;
+    data.write_to(Path::new(data_dir))?;
+    log(&format!(
+        "Imported {} gismu ({} with experimental rafsi) and {} cmavo with rafsi ({} with experimental rafsi) into {}",
+        data.gismu_rafsi.len(),
+        data.gismu_rafsi_exp.len(),
+        data.cmavo_rafsi.len(),
+        data.cmavo_rafsi_exp.len(),
+        data_dir
+    ));
+    Ok(())
+}
+
+#[cfg(feature = "server")]
+fn serve_command(m: &ArgMatches, ctx: &Context) -> Result<(), CliError> {
+    use vlazba::libs::server::{Server, ServerLimits};
+
+    let number = |id: &str| m.get_one::<u64>(id).copied();
+    let defaults = ServerLimits::default();
+    let limits = ServerLimits {
+        max_tanru_words: number("max_tanru_words").map_or(defaults.max_tanru_words, |n| n as usize),
+        max_candidates: number("max_candidates").unwrap_or(defaults.max_candidates),
+        timeout: number("timeout_ms").map_or(defaults.timeout, Duration::from_millis),
+        max_body_bytes: number("max_body_bytes").map_or(defaults.max_body_bytes, |n| n as usize),
+        ..defaults
+    };
+    let gismu_list = read_gismu_list(m.get_one::<String>("gismu_list").unwrap())?;
+    let server = Server::new(ctx.maps()?.clone(), ctx.exp_rafsi(m)?, gismu_list, limits);
+    let addr = m.get_one::<String>("addr").unwrap();
+    log(&format!("Listening on http://{}", addr));
+    server.serve(addr, number("threads").unwrap_or(4) as usize)?;
+    Ok(())
+}
+
+fn lujvo_command(m: &ArgMatches, ctx: &Context) -> Result<(), CliError> {
+    let forbid_la_lai_doi = m.get_flag("forbid_la_lai_doi");
+    let limits = JvozbaLimits {
+        max_components: m.get_one::<usize>("max_components").copied(),
+        max_candidates: m.get_one::<u64>("max_candidates").copied(),
+        timeout: m.get_one::<u64>("timeout_ms").map(|&ms| Duration::from_millis(ms)),
+    };
+    let scorer = m
+        .get_one::<String>("scorer")
+        .and_then(|name| scorer_by_name(name))
+        .ok_or_else(|| anyhow::anyhow!("unknown scorer"))?;
+    let dictionary = ctx.dictionary(ctx.exp_rafsi(m)?)?;
+    let explain = m.get_flag("explain");
+    let make_lujvo = |tanru: &str| -> Result<Vec<LujvoAndScore>, CliError> {
+        let words: Vec<String> = tanru.split_whitespace().map(|word| word.to_string()).collect();
+        if words.is_empty() {
+            return Err(VlazbaError::TooFewValsi { count: 0 }.into());
         }
-        return Ok(());
+        let results = try_jvozba(&words, forbid_la_lai_doi, false, &dictionary, scorer.as_ref(), &limits)?;
+        if results.is_empty() {
+            return Err(CliError::NoResult("No lujvo found".to_string()));
+        }
+        Ok(if explain {
+            results.into_iter().map(|result| result.explained()).collect()
+        } else {
+            results
+        })
+    };
+    if let Some(lines) = batch(m)? {
+        // Only the best lujvo of each tanru
+        return run_batch(ctx.format, &lines, |tanru| make_lujvo(tanru).map(|mut results| results.swap_remove(0)));
     }
+    print_records(ctx.format, &make_lujvo(words(m))?)
+}
 
-    let words: Vec<String> = matches
-        .get_one::<String>("words")
-        .map(|s| s.split_whitespace().map(|word| word.to_string()).collect())
-        .unwrap_or_default();
-    let all_letters = matches.contains_id("all-letters");
-    let shapes: Vec<String> = matches
-        .get_one::<String>("shapes")
-        .unwrap()
+fn reconstruct_command(m: &ArgMatches, ctx: &Context) -> Result<(), CliError> {
+    let forbid_cmevla = m.get_flag("forbid_cmevla");
+    let dictionary = ctx.dictionary(ctx.exp_rafsi(m)?)?;
+    let reconstruct = |lujvo: &str| -> Result<ReconstructedLujvo, CliError> {
+        Ok(ReconstructedLujvo {
+            lujvo: lujvo.to_string(),
+            reconstructed: jvozba::tools::reconstruct_lujvo(lujvo, forbid_cmevla, &dictionary)?,
+        })
+    };
+    if let Some(lines) = batch(m)? {
+        return run_batch(ctx.format, &lines, reconstruct);
+    }
+    print_records(ctx.format, &[reconstruct(words(m))?])
+}
+
+fn similar_command(m: &ArgMatches, ctx: &Context, gismu_list_path: &str) -> Result<(), CliError> {
+    let gismus = read_gismu_list(gismu_list_path)?;
+    let matcher = Arc::new(GismuMatcher::new(&gismus, None));
+    let find_similar = |candidate: &str| SimilarGismu {
+        candidate: candidate.to_string(),
+        similar: matcher.gimka(candidate),
+    };
+    if let Some(lines) = batch(m)? {
+        // An empty list of similar gismu is a result here, not a failure
+        return run_batch(ctx.format, &lines, |candidate| Ok(find_similar(candidate)));
+    }
+
+    log(&format!("Looking for gismu similar to '{}' using list: {}", words(m), gismu_list_path));
+    let similar = find_similar(words(m));
+    if similar.similar.is_empty() {
+        return Err(CliError::NoResult("No similar gismu found".to_string()));
+    }
+    print_records(ctx.format, &[similar])
+}
+
+fn split_command(m: &ArgMatches, ctx: &Context) -> Result<(), CliError> {
+    let dictionary = ctx.dictionary(ctx.exp_rafsi(m)?)?;
+    let decompose = |lujvo: &str| -> Result<_, CliError> { Ok(jvokaha_decomposition(lujvo, &dictionary)?) };
+    if let Some(lines) = batch(m)? {
+        return run_batch(ctx.format, &lines, decompose);
+    }
+    print_records(ctx.format, &[decompose(words(m))?])
+}
+
+fn gismu_command(m: &ArgMatches, ctx: &Context) -> Result<(), CliError> {
+    let words: Vec<String> = words(m).split_whitespace().map(|word| word.to_string()).collect();
+    let all_letters = m.get_flag("all-letters");
+    let settings = ctx.settings()?;
+    let shapes: Vec<String> = ctx
+        .gismu_option(m, "shapes", &settings.shapes)
         .split(',')
         .map(str::trim)
         .map(|s| s.to_string())
         .collect();
-    let weights = generate_weights(matches.get_one::<String>("weights").unwrap())?;
+    let weights = generate_weights(ctx.gismu_option(m, "weights", &settings.weights))
+        .map_err(CliError::InvalidInput)?;
 
-    let gismu_list_path = matches.get_one::<String>("deduplicate");
+    let gismu_list_path = m.get_one::<String>("deduplicate");
 
-    validate_words(&words, &weights)?;
+    validate_words(&words, &weights).map_err(CliError::InvalidInput)?;
 
     let (c, v) = if all_letters {
         (
@@ -247,17 +793,21 @@ fn main() -> anyhow::Result<()> {
 
     let scorer = GismuScorer::new(&words, &weights);
 
-    let mut scores: Vec<(f32, &String, SmallVec<[f32; 6]>)> = candidates
+    let mut scores: Vec<GismuCandidate> = candidates
     .par_iter()
-    .map(|candidate| scorer.compute_score_with_name(candidate))
+    .map(|candidate| scorer.score_candidate(candidate))
     .collect();
     
 
-    scores.sort_unstable_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    scores.sort_unstable_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
 
-    log("\n10 first gismu candidates are:\n");
-    for record in scores.iter().take(10) {
-        log(&format!("{:?}", record));
+    if scores.is_empty() {
+        return Err(CliError::NoResult("No gismu candidates generated".to_string()));
+    }
+    // Structured output holds the top candidates, or only the winner when deduplicating
+    if ctx.format == OutputFormat::Text || gismu_list_path.is_none() {
+        log("\n10 first gismu candidates are:\n");
+        print_records(ctx.format, &scores[..scores.len().min(10)])?;
     }
 
     if let Some(gismu_list_path) = gismu_list_path {
@@ -265,11 +815,14 @@ fn main() -> anyhow::Result<()> {
         let gismus = read_gismu_list(gismu_list_path)?;
         let matcher = Arc::new(GismuMatcher::new(&gismus, None));
         log("Excluding candidates similar to existing gismu...");
-        if let Some(candidate) = deduplicate_candidates(&matcher, &scores) {
-            log("The winner is....");
-            log(&candidate.to_uppercase().to_string());
+        let Some(winner) = deduplicate_candidates(&matcher, &scores) else {
+            return Err(CliError::NoResult("No suitable candidates found.".to_string()));
+        };
+        log("The winner is....");
+        if ctx.format == OutputFormat::Text {
+            println!("{}", winner.candidate.to_uppercase());
         } else {
-            log("No suitable candidates found.");
+            print_records(ctx.format, std::slice::from_ref(winner))?;
         }
     }
 
@@ -291,23 +844,51 @@ fn letters_for_words(words: &[String]) -> (Vec<String>, Vec<String>) {
     )
 }
 
-fn deduplicate_candidates(
+fn deduplicate_candidates<'a>(
     matcher: &Arc<GismuMatcher>,
-    scores: &[(f32, &String, SmallVec<[f32; 6]>)],
-) -> Option<String> {
-    scores.par_iter().find_map_any(|(_, candidate, _)| {
-        matcher.find_similar_gismu(candidate).map(|gismu| {
+    scores: &'a [GismuCandidate],
+) -> Option<&'a GismuCandidate> {
+    scores.par_iter().find_map_any(|record| {
+        matcher.find_similar_gismu(&record.candidate).map(|gismu| {
             log(&format!(
                 "Candidate '{}' too much like gismu '{}'.",
-                candidate, gismu
+                record.candidate, gismu
             ));
-            (*candidate).to_string()
+            record
         })
     })
 }
 
-fn read_gismu_list(path: &str) -> io::Result<Vec<String>> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    reader.lines().collect()
+/// Read the lines of a batch file, or of stdin for `-`
+fn read_batch(path: &str) -> Result<Vec<String>, CliError> {
+    let lines = if path == "-" {
+        io::stdin().lock().lines().collect()
+    } else {
+        File::open(path).and_then(|file| BufReader::new(file).lines().collect())
+    };
+    lines.map_err(|e| CliError::InvalidInput(anyhow::anyhow!("cannot read batch input {}: {}", path, e)))
+}
+
+/// Read a gismu list given on the command line; failing to do so is an input error
+fn read_gismu_list(path: &str) -> Result<Vec<String>, CliError> {
+    let read = || -> io::Result<Vec<String>> {
+        let reader = BufReader::new(File::open(path)?);
+        reader.lines().collect()
+    };
+    read().map_err(|e| CliError::InvalidInput(anyhow::anyhow!("cannot read gismu list {}: {}", path, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli() {
+        cli().debug_assert();
+        let matches = cli().try_get_matches_from(["vlazba", "--jvozba", "klama gasnu"]).unwrap();
+        assert!(matches.subcommand().is_none() && matches.get_flag("jvozba"));
+        let matches = cli().try_get_matches_from(["vlazba", "lujvo", "--batch", "-"]).unwrap();
+        assert_eq!(matches.subcommand_name(), Some("lujvo"));
+        assert!(cli().try_get_matches_from(["vlazba", "split", "--scorer", "cll", "klagau"]).is_err());
+    }
 }
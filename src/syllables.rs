@@ -0,0 +1,188 @@
+use std::ops::Range;
+
+use crate::jvozba::narge::is_c;
+use crate::libs::config::VALID_CC_INITIALS;
+
+/// A syllable of a Lojban word
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Syllable {
+    pub text: String,
+    pub stressed: bool,
+}
+
+/// Split a word into syllables and mark the stressed one
+///
+/// Diphthongs (`ai`, `ei`, `oi`, `au`) form a single syllable, and `i` or `u`
+/// before another vowel is a glide that starts the next syllable. An apostrophe
+/// always starts a new syllable. Consonants between vowels go to the following
+/// syllable as far as they form a permissible initial cluster.
+///
+/// Stress falls on the penultimate syllable, not counting syllables with `y`,
+/// unless the word marks stress itself with capital vowels.
+///
+/// # Examples
+///
+/// ```rust
+/// use vlazba::syllables::{mark_stress, syllable_count};
+///
+/// assert_eq!(mark_stress("klamyseltru"), "kla.my.SEL.tru");
+/// assert_eq!(syllable_count("bramlatu"), 3);
+/// ```
+pub fn syllabify(valsi: &str) -> Vec<Syllable> {
+    let letters: Vec<char> = valsi.chars().collect();
+    let lower: Vec<char> = valsi.to_lowercase().chars().collect();
+    if letters.len() != lower.len() {
+        return Vec::new();
+    }
+
+    let nuclei = find_nuclei(&lower);
+    if nuclei.is_empty() {
+        return Vec::new();
+    }
+
+    // Syllable i spans bounds[i]..bounds[i + 1]
+    let mut bounds = vec![0];
+    for pair in nuclei.windows(2) {
+        let (coda_start, onset_end) = (pair[0].end, pair[1].start);
+        bounds.push(onset_start(&lower, coda_start, onset_end));
+    }
+    bounds.push(letters.len());
+
+    let mut syllables: Vec<Syllable> = bounds
+        .windows(2)
+        .map(|b| Syllable {
+            text: letters[b[0]..b[1]].iter().collect(),
+            stressed: false,
+        })
+        .collect();
+
+    let marked: Vec<usize> = nuclei
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| letters[n.start..n.end].iter().any(|c| c.is_uppercase()))
+        .map(|(i, _)| i)
+        .collect();
+    if !marked.is_empty() {
+        for i in marked {
+            syllables[i].stressed = true;
+        }
+    } else {
+        let stressable: Vec<usize> = nuclei
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| lower[n.end - 1] != 'y')
+            .map(|(i, _)| i)
+            .collect();
+        if stressable.len() >= 2 {
+            syllables[stressable[stressable.len() - 2]].stressed = true;
+        }
+    }
+    syllables
+}
+
+/// Number of syllables in a word
+pub fn syllable_count(valsi: &str) -> usize {
+    syllabify(valsi).len()
+}
+
+/// Write a word with dots between syllables and the stressed syllable in capitals
+pub fn mark_stress(valsi: &str) -> String {
+    syllabify(valsi)
+        .into_iter()
+        .map(|s| if s.stressed { s.text.to_uppercase() } else { s.text.to_lowercase() })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Character ranges of the syllable nuclei: single vowels, y, or diphthongs, each
+/// together with a glide in front of it
+fn find_nuclei(letters: &[char]) -> Vec<Range<usize>> {
+    let is_vowel = |c: char| "aeiou".contains(c);
+    let mut nuclei = Vec::new();
+    let mut glide_start = None;
+    let mut i = 0;
+    while i < letters.len() {
+        let c = letters[i];
+        let next = letters.get(i + 1).copied();
+        let start = glide_start.take().unwrap_or(i);
+        if c == 'y' {
+            nuclei.push(start..i + 1);
+        } else if is_vowel(c) {
+            if matches!((c, next), ('a', Some('i' | 'u')) | ('e' | 'o', Some('i'))) {
+                nuclei.push(start..i + 2);
+                i += 1;
+            } else if (c == 'i' || c == 'u') && next.is_some_and(is_vowel) {
+                glide_start = Some(start);
+            } else {
+                nuclei.push(start..i + 1);
+            }
+        }
+        i += 1;
+    }
+    nuclei
+}
+
+/// Where the onset of the next syllable starts among the letters between two nuclei
+fn onset_start(letters: &[char], coda_start: usize, onset_end: usize) -> usize {
+    let mut start = onset_end;
+    while start > coda_start {
+        let c = letters[start - 1];
+        if c == '\'' {
+            return start - 1;
+        }
+        if !is_c(c) {
+            break;
+        }
+        if start < onset_end && is_c(letters[start]) {
+            let pair: String = [c, letters[start]].iter().collect();
+            if !VALID_CC_INITIALS.contains(&pair.as_str()) {
+                break;
+            }
+        }
+        start -= 1;
+    }
+    start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(valsi: &str) -> Vec<String> {
+        syllabify(valsi).into_iter().map(|s| s.text).collect()
+    }
+
+    #[test]
+    fn test_syllabify_gismu_and_lujvo() {
+        assert_eq!(texts("klama"), vec!["kla", "ma"]);
+        assert_eq!(texts("gasnu"), vec!["ga", "snu"]);
+        assert_eq!(texts("kalga'u"), vec!["kal", "ga", "'u"]);
+        assert_eq!(texts("bramlatu"), vec!["bra", "mla", "tu"]);
+        assert_eq!(texts("klamyseltru"), vec!["kla", "my", "sel", "tru"]);
+        assert_eq!(mark_stress("klagau"), "KLA.gau");
+        assert_eq!(mark_stress("bramlatu"), "bra.MLA.tu");
+    }
+
+    #[test]
+    fn test_y_hyphen_is_never_stressed() {
+        assert_eq!(mark_stress("klamyseltru"), "kla.my.SEL.tru");
+        assert_eq!(mark_stress("slakygau"), "SLA.ky.gau");
+    }
+
+    #[test]
+    fn test_apostrophes_and_glides() {
+        assert_eq!(texts("ca'irgau"), vec!["ca", "'ir", "gau"]);
+        assert_eq!(mark_stress("ca'irgau"), "ca.'IR.gau");
+        assert_eq!(texts("ie"), vec!["ie"]);
+        assert_eq!(texts("tsani"), vec!["tsa", "ni"]);
+        assert_eq!(texts("ckaji"), vec!["cka", "ji"]);
+        assert_eq!(texts("kuau"), vec!["kuau"]);
+    }
+
+    #[test]
+    fn test_explicit_stress() {
+        assert_eq!(mark_stress("iRAN"), "i.RAN");
+        assert_eq!(syllable_count("mlatu"), 2);
+        assert_eq!(syllable_count(""), 0);
+    }
+}
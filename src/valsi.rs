@@ -0,0 +1,256 @@
+use once_cell::sync::Lazy;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::error::VlazbaError;
+use crate::gismu_utils::GismuGenerator;
+use crate::jvozba::jvokaha::{is_slinkuhi, jvokaha};
+use crate::jvozba::narge::{is_c, is_cmevla, is_permissible};
+use crate::libs::config::{FORBIDDEN_CCC, VALID_CC_INITIALS};
+
+/// The bundled gismu list, one gismu per line
+pub(crate) const GISMU_LIST_TEXT: &str = include_str!("gismu-list.txt");
+
+static GISMU_LIST: Lazy<HashSet<&'static str>> = Lazy::new(|| {
+    GISMU_LIST_TEXT
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect()
+});
+
+type ShapeValidator = Box<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Consonant cluster checks of [`GismuGenerator`] for the two gismu shapes
+static GISMU_SHAPE_VALIDATORS: Lazy<[(&'static str, ShapeValidator); 2]> = Lazy::new(|| {
+    let generator = GismuGenerator::new(vec![], vec![], vec![]);
+    ["cvccv", "ccvcv"].map(|shape| (shape, Box::new(generator.shape_validator(shape)) as ShapeValidator))
+});
+
+/// Morphological class of a Lojban word, as determined by [`classify_valsi`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValsiClass {
+    /// A gismu; `official` is true if it is in the bundled gismu list
+    Gismu { official: bool },
+    Lujvo,
+    Cmavo,
+    /// Several cmavo written together, such as `lenu`
+    CmavoCompound(Vec<String>),
+    Cmevla,
+    Fuhivla,
+    Invalid(InvalidValsi),
+}
+
+/// Reason a string is not a valid Lojban word
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidValsi {
+    Empty,
+    UnknownLetter(char),
+    /// Consonants that may not stand next to each other
+    ImpermissibleConsonants(String),
+    /// A brivla starting with consonants that cannot start a word
+    BadInitialCluster(String),
+    /// Splits into rafsi, but the canonical lujvo for them is `correct`
+    MalformedLujvo { correct: String },
+    /// Contains y but is not a lujvo
+    YInBrivla,
+    /// Would break up into cmavo followed by another word
+    FallsApart,
+    /// Prefixing a CV cmavo would form a lujvo
+    Slinkuhi,
+}
+
+impl fmt::Display for InvalidValsi {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvalidValsi::Empty => write!(f, "empty word"),
+            InvalidValsi::UnknownLetter(c) => write!(f, "{{{}}} is not a Lojban letter", c),
+            InvalidValsi::ImpermissibleConsonants(cc) => {
+                write!(f, "{{{}}} is not a permissible consonant cluster", cc)
+            }
+            InvalidValsi::BadInitialCluster(cc) => write!(f, "{{{}}} cannot start a word", cc),
+            InvalidValsi::MalformedLujvo { correct } => {
+                write!(f, "malformed lujvo; it should be {{{}}}", correct)
+            }
+            InvalidValsi::YInBrivla => write!(f, "only lujvo may contain y"),
+            InvalidValsi::FallsApart => write!(f, "breaks up into cmavo and another word"),
+            InvalidValsi::Slinkuhi => write!(f, "fails the slinku'i test"),
+        }
+    }
+}
+
+/// Classify a word as gismu, lujvo, cmavo, cmevla, fu'ivla or invalid
+///
+/// Capital letters, which mark irregular stress, are treated as lower case.
+///
+/// # Examples
+///
+/// ```rust
+/// use vlazba::valsi::{classify_valsi, ValsiClass};
+///
+/// assert_eq!(classify_valsi("klama"), ValsiClass::Gismu { official: true });
+/// assert_eq!(classify_valsi("klagau"), ValsiClass::Lujvo);
+/// assert_eq!(classify_valsi("spageti"), ValsiClass::Fuhivla);
+/// ```
+pub fn classify_valsi(word: &str) -> ValsiClass {
+    let word = word.to_lowercase();
+    if word.is_empty() {
+        return ValsiClass::Invalid(InvalidValsi::Empty);
+    }
+    if let Some(c) = word.chars().find(|&c| !is_c(c) && !"aeiouy'".contains(c)) {
+        return ValsiClass::Invalid(InvalidValsi::UnknownLetter(c));
+    }
+
+    if is_cmevla(&word) {
+        return ValsiClass::Cmevla;
+    }
+
+    // Apostrophes separate vowels and never take part in clusters
+    let letters: Vec<char> = word.chars().filter(|&c| c != '\'').collect();
+    let Some(cluster) = letters.windows(2).position(|w| is_c(w[0]) && is_c(w[1])) else {
+        return classify_cmavo(&word);
+    };
+    if cluster + 2 > 5 {
+        return ValsiClass::Invalid(InvalidValsi::FallsApart);
+    }
+
+    if word.len() == 5 && !word.contains(['y', '\'']) {
+        if let Some(class) = classify_gismu(&word) {
+            return class;
+        }
+    }
+
+    match jvokaha(&word) {
+        Ok(_) => return ValsiClass::Lujvo,
+        Err(VlazbaError::NonCanonical { correct, .. }) => {
+            return ValsiClass::Invalid(InvalidValsi::MalformedLujvo { correct })
+        }
+        Err(_) => {}
+    }
+
+    classify_fuhivla(&word, &letters, cluster)
+}
+
+fn classify_gismu(word: &str) -> Option<ValsiClass> {
+    let shape: String = word
+        .chars()
+        .map(|c| if is_c(c) { 'c' } else { 'v' })
+        .collect();
+    if shape != "cvccv" && shape != "ccvcv" {
+        return None;
+    }
+
+    let (_, validator) = GISMU_SHAPE_VALIDATORS.iter().find(|(s, _)| *s == shape)?;
+    if validator(word) {
+        Some(ValsiClass::Gismu {
+            official: GISMU_LIST.contains(word),
+        })
+    } else if shape == "ccvcv" {
+        Some(ValsiClass::Invalid(InvalidValsi::BadInitialCluster(word[..2].to_string())))
+    } else {
+        Some(ValsiClass::Invalid(InvalidValsi::ImpermissibleConsonants(
+            word[2..4].to_string(),
+        )))
+    }
+}
+
+fn classify_cmavo(word: &str) -> ValsiClass {
+    let mut cmavo: Vec<String> = Vec::new();
+    for c in word.chars() {
+        match cmavo.last_mut() {
+            Some(current) if !is_c(c) => current.push(c),
+            _ => cmavo.push(c.to_string()),
+        }
+    }
+
+    if cmavo.len() == 1 {
+        ValsiClass::Cmavo
+    } else {
+        ValsiClass::CmavoCompound(cmavo)
+    }
+}
+
+fn classify_fuhivla(word: &str, letters: &[char], cluster: usize) -> ValsiClass {
+    if word.contains('y') {
+        return ValsiClass::Invalid(InvalidValsi::YInBrivla);
+    }
+
+    for pair in letters.windows(2).filter(|w| is_c(w[0]) && is_c(w[1])) {
+        if is_permissible(pair[0], pair[1]) == 0 {
+            return ValsiClass::Invalid(InvalidValsi::ImpermissibleConsonants(
+                pair.iter().collect(),
+            ));
+        }
+    }
+    for triple in letters.windows(3) {
+        let triple: String = triple.iter().collect();
+        if FORBIDDEN_CCC.contains(&triple.as_str()) {
+            return ValsiClass::Invalid(InvalidValsi::ImpermissibleConsonants(triple));
+        }
+    }
+
+    let initial: String = letters[cluster..cluster + 2].iter().collect();
+    if cluster == 0 {
+        if !VALID_CC_INITIALS.contains(&initial.as_str()) {
+            return ValsiClass::Invalid(InvalidValsi::BadInitialCluster(initial));
+        }
+    } else if VALID_CC_INITIALS.contains(&initial.as_str()) {
+        // The cmavo before the cluster would fall off if the rest is a word of its own
+        let rest: String = letters[cluster..].iter().collect();
+        if matches!(
+            classify_valsi(&rest),
+            ValsiClass::Gismu { .. } | ValsiClass::Lujvo | ValsiClass::Fuhivla
+        ) {
+            return ValsiClass::Invalid(InvalidValsi::FallsApart);
+        }
+    }
+
+    if is_slinkuhi(word) {
+        return ValsiClass::Invalid(InvalidValsi::Slinkuhi);
+    }
+    ValsiClass::Fuhivla
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_brivla() {
+        assert_eq!(classify_valsi("klama"), ValsiClass::Gismu { official: true });
+        assert_eq!(classify_valsi("Klama"), ValsiClass::Gismu { official: true });
+        assert_eq!(classify_valsi("blaku"), ValsiClass::Gismu { official: false });
+        assert_eq!(classify_valsi("bramlatu"), ValsiClass::Lujvo);
+        assert_eq!(classify_valsi("klamyseltru"), ValsiClass::Lujvo);
+        assert_eq!(classify_valsi("spageti"), ValsiClass::Fuhivla);
+    }
+
+    #[test]
+    fn test_classify_cmavo_and_cmevla() {
+        assert_eq!(classify_valsi("le"), ValsiClass::Cmavo);
+        assert_eq!(classify_valsi("ku'o"), ValsiClass::Cmavo);
+        assert_eq!(classify_valsi("i"), ValsiClass::Cmavo);
+        assert_eq!(
+            classify_valsi("lenu"),
+            ValsiClass::CmavoCompound(vec!["le".to_string(), "nu".to_string()])
+        );
+        assert_eq!(classify_valsi("djan"), ValsiClass::Cmevla);
+    }
+
+    #[test]
+    fn test_classify_invalid() {
+        assert_eq!(classify_valsi(""), ValsiClass::Invalid(InvalidValsi::Empty));
+        assert_eq!(classify_valsi("qwerti"), ValsiClass::Invalid(InvalidValsi::UnknownLetter('q')));
+        assert_eq!(
+            classify_valsi("toibroda"),
+            ValsiClass::Invalid(InvalidValsi::MalformedLujvo { correct: "toirbroda".to_string() })
+        );
+        assert_eq!(classify_valsi("pastuno"), ValsiClass::Invalid(InvalidValsi::FallsApart));
+        assert_eq!(classify_valsi("lenulujvo"), ValsiClass::Invalid(InvalidValsi::FallsApart));
+        assert_eq!(
+            classify_valsi("tlaxa"),
+            ValsiClass::Invalid(InvalidValsi::BadInitialCluster("tl".to_string()))
+        );
+        assert_eq!(classify_valsi("spagyti"), ValsiClass::Invalid(InvalidValsi::YInBrivla));
+    }
+}
@@ -0,0 +1,202 @@
+use std::borrow::Cow;
+
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::error::VlazbaError;
+use crate::gismu_utils::{is_lojban_candidate, GismuMatcher, SimilarGismu};
+use crate::jvozba::dictionary::RafsiDictionary;
+use crate::jvozba::jvokaha::{jvokaha_decomposition, LujvoDecomposition};
+use crate::jvozba::rafsi_list::RafsiMap;
+use crate::jvozba::scoring::{scorer_by_name, Cll1994, LujvoScorer, SCORER_NAMES};
+use crate::jvozba::tools::{self, RafsiOptions};
+use crate::jvozba::{try_jvozba, JvozbaLimits, LujvoAndScore};
+use crate::valsi::GISMU_LIST_TEXT;
+
+/// Candidates jvozba may look at when `max_candidates` is not given, as for the HTTP server
+const DEFAULT_MAX_CANDIDATES: u64 = 1_000_000;
+
+static DICTIONARY: Lazy<RafsiDictionary> = Lazy::new(RafsiDictionary::official);
+static DICTIONARY_EXP: Lazy<RafsiDictionary> = Lazy::new(RafsiDictionary::with_experimental);
+static GISMU_LIST: Lazy<Vec<String>> = Lazy::new(|| {
+    GISMU_LIST_TEXT
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect()
+});
+
+/// Options of the JavaScript functions, given as an object whose fields may all be left out
+///
+/// The rafsi fields are those of [`RafsiOptions`]. Browsers have no clock to time a
+/// search with, so jvozba is limited by `max_candidates` only.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Options {
+    exp_rafsi: bool,
+    custom_gismu: Option<RafsiMap>,
+    custom_gismu_exp: Option<RafsiMap>,
+    custom_cmavo: Option<RafsiMap>,
+    custom_cmavo_exp: Option<RafsiMap>,
+    forbid_la_lai_doi: bool,
+    forbid_cmevla: bool,
+    /// One of [`SCORER_NAMES`], `cll` if not given
+    scorer: Option<String>,
+    /// [`DEFAULT_MAX_CANDIDATES`] if not given
+    max_candidates: Option<u64>,
+}
+
+impl Options {
+    /// The bundled dictionaries are built once; custom maps need a dictionary of their own
+    fn dictionary(&self) -> Cow<'static, RafsiDictionary> {
+        let custom = [&self.custom_gismu, &self.custom_gismu_exp, &self.custom_cmavo, &self.custom_cmavo_exp]
+            .iter()
+            .any(|map| map.is_some());
+        match (custom, self.exp_rafsi) {
+            (false, false) => Cow::Borrowed(&DICTIONARY),
+            (false, true) => Cow::Borrowed(&DICTIONARY_EXP),
+            (true, _) => Cow::Owned(RafsiDictionary::from_options(&RafsiOptions {
+                exp_rafsi: self.exp_rafsi,
+                custom_cmavo: self.custom_cmavo.as_ref(),
+                custom_cmavo_exp: self.custom_cmavo_exp.as_ref(),
+                custom_gismu: self.custom_gismu.as_ref(),
+                custom_gismu_exp: self.custom_gismu_exp.as_ref(),
+            })),
+        }
+    }
+
+    fn limits(&self) -> JvozbaLimits {
+        JvozbaLimits {
+            max_candidates: Some(self.max_candidates.unwrap_or(DEFAULT_MAX_CANDIDATES)),
+            ..JvozbaLimits::default()
+        }
+    }
+}
+
+/// Lujvo for a tanru of words separated by spaces, best first
+///
+/// Each result has `lujvo`, `score`, `scorer` and `rafsi`.
+#[wasm_bindgen]
+pub fn jvozba(tanru: &str, options: JsValue) -> Result<JsValue, JsError> {
+    let options: Options = from_js(options)?;
+    let scorer = scorer_by_name(options.scorer.as_deref().unwrap_or("cll"))
+        .ok_or_else(|| JsError::new(&format!("unknown scorer, expected one of {}", SCORER_NAMES.join(", "))))?;
+    to_js(&make_lujvo(tanru, &options, scorer.as_ref())?)
+}
+
+/// The rafsi of a lujvo and the selrafsi of each, `null` where it is unknown
+#[wasm_bindgen]
+pub fn jvokaha(lujvo: &str, options: JsValue) -> Result<JsValue, JsError> {
+    to_js(&split_lujvo(lujvo, &from_js(options)?)?)
+}
+
+/// The canonical form of a lujvo, made again from its components
+#[wasm_bindgen]
+pub fn reconstruct_lujvo(lujvo: &str, options: JsValue) -> Result<String, JsError> {
+    Ok(remake_lujvo(lujvo, &from_js(options)?)?)
+}
+
+/// The best selrafsi for a rafsi, `undefined` if no word has it
+#[wasm_bindgen]
+pub fn search_selrafsi_from_rafsi2(rafsi: &str, options: JsValue) -> Result<Option<String>, JsError> {
+    let options: Options = from_js(options)?;
+    Ok(tools::search_selrafsi_from_rafsi2(rafsi, options.dictionary().as_ref()))
+}
+
+/// Existing gismu too similar to a candidate, from `gismu_list` or else the bundled list
+#[wasm_bindgen]
+pub fn gimka(candidate: &str, gismu_list: JsValue) -> Result<JsValue, JsError> {
+    let gismu_list: Option<Vec<String>> = from_js(gismu_list)?;
+    to_js(&similar_gismu(candidate, gismu_list.as_deref()).map_err(|e| JsError::new(&e))?)
+}
+
+fn make_lujvo(
+    tanru: &str,
+    options: &Options,
+    scorer: &dyn LujvoScorer,
+) -> Result<Vec<LujvoAndScore>, VlazbaError> {
+    let words: Vec<String> = tanru.split_whitespace().map(|word| word.to_string()).collect();
+    if words.len() < 2 {
+        return Err(VlazbaError::TooFewValsi { count: words.len() });
+    }
+    try_jvozba(
+        &words,
+        options.forbid_la_lai_doi,
+        false,
+        options.dictionary().as_ref(),
+        scorer,
+        &options.limits(),
+    )
+}
+
+fn remake_lujvo(lujvo: &str, options: &Options) -> Result<String, VlazbaError> {
+    tools::try_reconstruct_lujvo(
+        lujvo,
+        options.forbid_cmevla,
+        options.dictionary().as_ref(),
+        &Cll1994,
+        &options.limits(),
+    )
+}
+
+fn split_lujvo(lujvo: &str, options: &Options) -> Result<LujvoDecomposition, VlazbaError> {
+    jvokaha_decomposition(lujvo, options.dictionary().as_ref())
+}
+
+fn similar_gismu(candidate: &str, gismu_list: Option<&[String]>) -> Result<SimilarGismu, String> {
+    if !is_lojban_candidate(candidate) {
+        return Err(format!("{} is not made of Lojban letters", candidate));
+    }
+    let matcher = GismuMatcher::new(gismu_list.unwrap_or(&GISMU_LIST), None);
+    Ok(SimilarGismu {
+        candidate: candidate.to_string(),
+        similar: matcher.gimka(candidate),
+    })
+}
+
+/// `undefined` and `null` stand for the default
+fn from_js<T: DeserializeOwned + Default>(value: JsValue) -> Result<T, JsError> {
+    if value.is_undefined() || value.is_null() {
+        return Ok(T::default());
+    }
+    Ok(serde_wasm_bindgen::from_value(value)?)
+}
+
+/// Results become plain objects and arrays, as `JSON.parse` would give them
+fn to_js(value: &impl Serialize) -> Result<JsValue, JsError> {
+    Ok(value.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lujvo_functions() {
+        let options = Options::default();
+        assert_eq!(make_lujvo("klama gasnu", &options, &Cll1994).unwrap()[0].lujvo, "klagau");
+        assert_eq!(split_lujvo("klagau", &options).unwrap().selrafsi[1].as_deref(), Some("gasnu"));
+        assert!(matches!(make_lujvo("klama", &options, &Cll1994), Err(VlazbaError::TooFewValsi { count: 1 })));
+
+        let custom: Options =
+            serde_json::from_str(r#"{"custom_gismu": {"klama": ["kam"], "gasnu": ["gau"]}, "max_candidates": 100}"#)
+                .unwrap();
+        assert_eq!(make_lujvo("klama gasnu", &custom, &Cll1994).unwrap()[0].lujvo, "kamgau");
+        assert!(serde_json::from_str::<Options>(r#"{"colour": "blue"}"#).is_err());
+
+        assert_eq!(remake_lujvo("klagasnu", &options).unwrap(), "klagau");
+        let limited: Options = serde_json::from_str(r#"{"max_candidates": 1}"#).unwrap();
+        let lujvo = format!("{}gau", "kla".repeat(4));
+        assert!(matches!(remake_lujvo(&lujvo, &limited), Err(VlazbaError::SearchLimit(_))));
+    }
+
+    #[test]
+    fn test_similar_gismu() {
+        assert!(similar_gismu("bakna", None).unwrap().similar.contains(&"bakni".to_string()));
+        let list = vec!["klama".to_string()];
+        assert!(similar_gismu("bakna", Some(&list)).unwrap().similar.is_empty());
+        assert!(similar_gismu("kléma", None).is_err());
+    }
+}